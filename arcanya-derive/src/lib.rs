@@ -0,0 +1,223 @@
+//! Derives for `arcanya::convert::{IntoExpression, FromExpression}`.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Generics, Ident, Index};
+
+#[proc_macro_derive(IntoExpression)]
+pub fn derive_into_expression(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let generics = bounded(&input.generics, quote!(::arcanya::convert::IntoExpression));
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let entries = fields.named.iter().map(|field| {
+                    let ident = field.ident.as_ref().unwrap();
+
+                    entry(&ident.to_string(), quote!(self.#ident))
+                });
+
+                table(entries)
+            }
+            Fields::Unnamed(fields) => {
+                let elements = (0..fields.unnamed.len()).map(|i| {
+                    let index = Index::from(i);
+
+                    quote!(::arcanya::convert::IntoExpression::into_expression(self.#index))
+                });
+
+                quote!(::arcanya::expression::Expression::List(
+                    vec![#(#elements),*].into()
+                ))
+            }
+            Fields::Unit => quote!(::arcanya::expression::Expression::Nil),
+        },
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let ident = &variant.ident;
+                let tag = entry("variant", ident.to_string().to_token_stream());
+
+                match &variant.fields {
+                    Fields::Named(fields) => {
+                        let idents = fields
+                            .named
+                            .iter()
+                            .map(|field| field.ident.clone().unwrap())
+                            .collect::<Vec<Ident>>();
+                        let entries = idents
+                            .iter()
+                            .map(|ident| entry(&ident.to_string(), quote!(#ident)));
+                        let table = table(std::iter::once(tag).chain(entries));
+
+                        quote!(Self::#ident { #(#idents),* } => #table)
+                    }
+                    Fields::Unnamed(fields) => {
+                        let idents = (0..fields.unnamed.len())
+                            .map(|i| Ident::new(&format!("field{i}"), Span::call_site()))
+                            .collect::<Vec<Ident>>();
+                        let entries = idents
+                            .iter()
+                            .enumerate()
+                            .map(|(i, ident)| entry(&i.to_string(), quote!(#ident)));
+                        let table = table(std::iter::once(tag).chain(entries));
+
+                        quote!(Self::#ident(#(#idents),*) => #table)
+                    }
+                    Fields::Unit => {
+                        let table = table(std::iter::once(tag));
+
+                        quote!(Self::#ident => #table)
+                    }
+                }
+            });
+
+            quote!(match self { #(#arms,)* })
+        }
+        Data::Union(_) => {
+            return syn::Error::new_spanned(name, "unions can't be converted to expressions")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    quote! {
+        impl #impl_generics ::arcanya::convert::IntoExpression for #name #type_generics #where_clause {
+            fn into_expression(self) -> ::arcanya::expression::Expression {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+#[proc_macro_derive(FromExpression)]
+pub fn derive_from_expression(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let generics = bounded(&input.generics, quote!(::arcanya::convert::FromExpression));
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let fields = fields.named.iter().map(|field| {
+                    let ident = field.ident.as_ref().unwrap();
+                    let key = ident.to_string();
+
+                    quote!(#ident: ::arcanya::convert::field(&table, #key)?)
+                });
+
+                quote! {
+                    let table = expression.as_table()?;
+
+                    Ok(Self { #(#fields),* })
+                }
+            }
+            Fields::Unnamed(fields) => {
+                let elements = (0..fields.unnamed.len())
+                    .map(|i| quote!(::arcanya::convert::element(&list, #i)?));
+
+                quote! {
+                    let list = expression.as_list()?;
+
+                    Ok(Self(#(#elements),*))
+                }
+            }
+            Fields::Unit => quote! {
+                <() as ::arcanya::convert::FromExpression>::from_expression(expression)?;
+
+                Ok(Self)
+            },
+        },
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let ident = &variant.ident;
+                let tag = ident.to_string();
+
+                match &variant.fields {
+                    Fields::Named(fields) => {
+                        let fields = fields.named.iter().map(|field| {
+                            let ident = field.ident.as_ref().unwrap();
+                            let key = ident.to_string();
+
+                            quote!(#ident: ::arcanya::convert::field(&table, #key)?)
+                        });
+
+                        quote!(#tag => Ok(Self::#ident { #(#fields),* }))
+                    }
+                    Fields::Unnamed(fields) => {
+                        let fields = (0..fields.unnamed.len()).map(|i| {
+                            let key = i.to_string();
+
+                            quote!(::arcanya::convert::field(&table, #key)?)
+                        });
+
+                        quote!(#tag => Ok(Self::#ident(#(#fields),*)))
+                    }
+                    Fields::Unit => quote!(#tag => Ok(Self::#ident)),
+                }
+            });
+            let expected = name.to_string();
+
+            quote! {
+                let (variant, table) = ::arcanya::convert::variant(expression)?;
+
+                match variant.as_str() {
+                    #(#arms,)*
+                    _ => Err(::arcanya::convert::unknown_variant(&variant, #expected)),
+                }
+            }
+        }
+        Data::Union(_) => {
+            return syn::Error::new_spanned(name, "unions can't be converted from expressions")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    quote! {
+        impl #impl_generics ::arcanya::convert::FromExpression for #name #type_generics #where_clause {
+            fn from_expression(
+                expression: &::arcanya::expression::Expression,
+            ) -> ::arcanya::convert::Result<Self> {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+/// `generics` with every type parameter required to implement `bound`.
+fn bounded(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    let parameters = generics
+        .type_params()
+        .map(|parameter| parameter.ident.clone())
+        .collect::<Vec<Ident>>();
+
+    let where_clause = generics.make_where_clause();
+
+    for parameter in parameters {
+        where_clause
+            .predicates
+            .push(parse_quote!(#parameter: #bound));
+    }
+
+    generics
+}
+
+/// A `(key, expression)` pair of a table.
+fn entry(key: &str, value: TokenStream2) -> TokenStream2 {
+    quote!((
+        ::arcanya::expression::Key::from(#key),
+        ::arcanya::convert::IntoExpression::into_expression(#value)
+    ))
+}
+
+fn table(entries: impl Iterator<Item = TokenStream2>) -> TokenStream2 {
+    quote!(::arcanya::expression::Expression::Table([#(#entries),*].into_iter().collect()))
+}
@@ -1,66 +1,93 @@
-use std::{cell::RefCell, rc::Rc, sync::atomic::Ordering};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use hashbrown::HashMap;
 
-use crate::{env::Env, eval::*, expression::Expression, run};
-use color_eyre::{eyre::eyre, Result};
+mod grapheme;
+mod json;
+mod regex;
+mod string;
+mod table;
+
+use crate::{
+    env::{CapturedEnv, Env},
+    error::{caught, error, throw, ErrorKind},
+    eval::*,
+    expression::{Arity, Expression, Key, List, Table},
+    run_source, source,
+};
+use color_eyre::{eyre::eyre, Report, Result};
 
 const PLUS: Expression = Expression::Builtin {
     name: "+",
+    arity: Arity::at_least(2),
     function: |env, list| {
         let evaluated = list.iter().map(|e| eval_expression(env, e));
 
-        Ok(evaluated
+        evaluated
             .reduce(|acc, x| acc? + x?)
-            .ok_or(eyre!("Missing parameters"))??)
+            .ok_or(eyre!("Missing parameters"))?
     },
 };
 
+/// `(- x)` negates `x`, while `(- x y ...)` subtracts the rest from `x`.
 const MINUS: Expression = Expression::Builtin {
     name: "-",
+    arity: Arity::at_least(1),
     function: |env, list| {
+        if let [x] = list {
+            return Expression::Integer(0) - eval_expression(env, x)?;
+        }
+
         let evaluated = list.iter().map(|e| eval_expression(env, e));
 
-        Ok(evaluated
+        evaluated
             .reduce(|acc, x| acc? - x?)
-            .ok_or(eyre!("Missing parameters"))??)
+            .ok_or(eyre!("Missing parameters"))?
     },
 };
 
 const MULTIPLY: Expression = Expression::Builtin {
     name: "*",
+    arity: Arity::at_least(2),
     function: |env, list| {
         let evaluated = list.iter().map(|e| eval_expression(env, e));
 
-        Ok(evaluated
+        evaluated
             .reduce(|acc, x| acc? * x?)
-            .ok_or(eyre!("Missing parameters"))??)
+            .ok_or(eyre!("Missing parameters"))?
     },
 };
 
 const DIVIDE: Expression = Expression::Builtin {
     name: "/",
+    arity: Arity::at_least(2),
     function: |env, list| {
         let evaluated = list.iter().map(|e| eval_expression(env, e));
 
-        Ok(evaluated
+        evaluated
             .reduce(|acc, x| acc? / x?)
-            .ok_or(eyre!("Missing parameters"))??)
+            .ok_or(eyre!("Missing parameters"))?
     },
 };
 
 const MOD: Expression = Expression::Builtin {
     name: "%",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let first = eval_expression(env, &list[0])?;
         let second = eval_expression(env, &list[1])?;
 
-        Ok(Expression::Integer(first.as_i64()? % second.as_i64()?))
+        first % second
     },
 };
 
 const FUNCTION: Expression = Expression::Builtin {
     name: "function",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let args = eval_expression(env, &list[0])?;
         let body = eval_expression(env, &list[1])?;
@@ -68,70 +95,156 @@ const FUNCTION: Expression = Expression::Builtin {
         Ok(Expression::Function {
             arguments: args.as_list()?,
             body: Box::new(body),
+            env: CapturedEnv(env.clone()),
+            name: None,
         })
     },
 };
 
-const IF: Expression = Expression::Builtin {
+const DEFMACRO: Expression = Expression::Builtin {
+    name: "defmacro",
+    arity: Arity::exactly(3),
+    function: |env, list| {
+        let name = eval_expression(env, &list[0])?.as_symbol_string()?;
+        let args = eval_expression(env, &list[1])?;
+        let body = eval_expression(env, &list[2])?;
+
+        let value = Expression::Macro {
+            arguments: args.as_list()?,
+            body: Box::new(body),
+            env: CapturedEnv(env.clone()),
+        };
+
+        env.as_ref().borrow_mut().set_global(name, value);
+
+        Ok(Expression::Nil)
+    },
+};
+
+const MACROEXPAND: Expression = Expression::Builtin {
+    name: "macroexpand",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let mut form = eval_expression(env, &list[0])?;
+
+        while let Expression::List(ref l) = form {
+            let Some(Expression::Macro {
+                arguments,
+                body,
+                env: captured,
+            }) = l
+                .first()
+                .map(|head| eval_expression(env, head))
+                .transpose()?
+            else {
+                break;
+            };
+
+            form = expand_macro(&arguments, &body, &captured, &l[1..])?;
+        }
+
+        Ok(form)
+    },
+};
+
+static GENSYM_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+const GENSYM: Expression = Expression::Builtin {
+    name: "gensym",
+    arity: Arity::between(0, 1),
+    function: |env, list| {
+        let prefix = match list.first() {
+            Some(prefix) => eval_expression(env, prefix)?.as_string()?,
+            None => "G".to_string(),
+        };
+
+        Ok(Expression::Symbol(format!(
+            "__{prefix}{}",
+            GENSYM_COUNT.fetch_add(1, Ordering::SeqCst)
+        )))
+    },
+};
+
+const IF: Expression = Expression::SpecialForm {
     name: "if",
+    arity: Arity::between(2, 3),
     function: |env, list| {
         let condition = eval_expression(env, &list[0])?;
         let has_else = list.len() > 2;
 
         if condition.as_boolean()? {
-            eval_expression(env, &list[1])
+            Ok(Tail::Eval(env.clone(), list[1].clone()))
         } else if has_else {
-            eval_expression(env, &list[2])
+            Ok(Tail::Eval(env.clone(), list[2].clone()))
         } else {
-            Ok(Expression::Nil)
+            Ok(Tail::Value(Expression::Nil))
         }
     },
 };
 
+/// Gives an unnamed function the name it is being defined under.
+fn named(value: Expression, name: &str) -> Expression {
+    match value {
+        Expression::Function {
+            arguments,
+            body,
+            env,
+            name: None,
+        } => Expression::Function {
+            arguments,
+            body,
+            env,
+            name: Some(name.to_string()),
+        },
+        value => value,
+    }
+}
+
 const DEFINE: Expression = Expression::Builtin {
     name: "define",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let name = eval_expression(env, &list[0])?;
 
         if let Expression::Symbol(_) = name {
-            let evaluated = eval_expression(env, &list[1])?;
+            let name = name.as_symbol_string()?;
+            let evaluated = named(eval_expression(env, &list[1])?, &name);
 
-            env.as_ref()
-                .borrow_mut()
-                .set_global(name.as_symbol_string()?, evaluated);
+            env.as_ref().borrow_mut().set_global(name, evaluated);
         }
 
         Ok(Expression::Nil)
     },
 };
 
-const LET: Expression = Expression::Builtin {
+const LET: Expression = Expression::SpecialForm {
     name: "let",
+    arity: Arity::exactly(3),
     function: |env, list| {
         let name = &eval_expression(env, &list[0])?;
         let value = eval_expression(env, &list[1])?;
 
-        let mut local_env = Rc::new(RefCell::new(Env::new(Some(env.clone()))));
+        let local_env = Rc::new(RefCell::new(Env::new(Some(env.clone()))));
         local_env
             .as_ref()
             .borrow_mut()
             .set_local(name.as_symbol_string()?, value);
 
-        eval_expression(&mut local_env, &list[2])
+        Ok(Tail::Eval(local_env, list[2].clone()))
     },
 };
 
 const DEFINE_LOCAL: Expression = Expression::Builtin {
     name: "define-local",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let name = eval_expression(env, &list[0])?;
 
         if let Expression::Symbol(_) = name {
-            let evaluated = eval_expression(env, &list[1])?;
+            let name = name.as_symbol_string()?;
+            let evaluated = named(eval_expression(env, &list[1])?, &name);
 
-            env.as_ref()
-                .borrow_mut()
-                .set_local(name.as_symbol_string()?, evaluated);
+            env.as_ref().borrow_mut().set_local(name, evaluated);
         }
 
         Ok(Expression::Nil)
@@ -140,11 +253,12 @@ const DEFINE_LOCAL: Expression = Expression::Builtin {
 
 const LIST: Expression = Expression::Builtin {
     name: "list",
+    arity: Arity::at_least(0),
     function: |env, list| {
         Ok(Expression::List(
             list.iter()
                 .map(|x| eval_expression(env, x))
-                .collect::<Result<Vec<Expression>>>()?,
+                .collect::<Result<List>>()?,
         ))
     },
 };
@@ -167,67 +281,73 @@ const LIST: Expression = Expression::Builtin {
 
 const EQUAL: Expression = Expression::Builtin {
     name: "=",
+    arity: Arity::at_least(2),
     function: |env, list| {
         let evaluated = list
             .iter()
-            .flat_map(|e| eval_expression(env, e))
-            .collect::<Vec<Expression>>();
+            .map(|e| eval_expression(env, e))
+            .collect::<Result<Vec<Expression>>>()?;
 
-        Ok(evaluated[1..].iter().all(|x| evaluated[0] == *x).into())
+        Ok(evaluated[1..].iter().all(|x| evaluated[0].equals(x)).into())
     },
 };
 
 const GREATER: Expression = Expression::Builtin {
     name: ">",
+    arity: Arity::at_least(2),
     function: |env, list| {
         let evaluated = list.iter().map(|e| eval_expression(env, e));
 
-        Ok(evaluated
+        evaluated
             .reduce(|acc, x| Ok((acc? > x?).into()))
-            .ok_or(eyre!("Missing parameters"))??)
+            .ok_or(eyre!("Missing parameters"))?
     },
 };
 
 const GREATER_EQUAL: Expression = Expression::Builtin {
     name: ">=",
+    arity: Arity::at_least(2),
     function: |env, list| {
         let evaluated = list.iter().map(|e| eval_expression(env, e));
 
-        Ok(evaluated
+        evaluated
             .reduce(|acc, x| Ok((acc? >= x?).into()))
-            .ok_or(eyre!("Missing parameters"))??)
+            .ok_or(eyre!("Missing parameters"))?
     },
 };
 
 const LESS: Expression = Expression::Builtin {
     name: "<",
+    arity: Arity::at_least(2),
     function: |env, list| {
         let evaluated = list.iter().map(|e| eval_expression(env, e));
 
-        Ok(evaluated
+        evaluated
             .reduce(|acc, x| Ok((acc? < x?).into()))
-            .ok_or(eyre!("Missing parameters"))??)
+            .ok_or(eyre!("Missing parameters"))?
     },
 };
 
 const LESS_EQUAL: Expression = Expression::Builtin {
     name: "<=",
+    arity: Arity::at_least(2),
     function: |env, list| {
         let evaluated = list.iter().map(|e| eval_expression(env, e));
 
-        Ok(evaluated
+        evaluated
             .reduce(|acc, x| Ok((acc? <= x?).into()))
-            .ok_or(eyre!("Missing parameters"))??)
+            .ok_or(eyre!("Missing parameters"))?
     },
 };
 
 const AND: Expression = Expression::Builtin {
     name: "and",
+    arity: Arity::at_least(1),
     function: |env, list| {
         let evaluated = list
             .iter()
-            .flat_map(|e| eval_expression(env, e))
-            .collect::<Vec<Expression>>();
+            .map(|e| eval_expression(env, e))
+            .collect::<Result<Vec<Expression>>>()?;
 
         Ok(evaluated[1..]
             .iter()
@@ -239,11 +359,12 @@ const AND: Expression = Expression::Builtin {
 
 const OR: Expression = Expression::Builtin {
     name: "or",
+    arity: Arity::at_least(1),
     function: |env, list| {
         let evaluated = list
             .iter()
-            .flat_map(|e| eval_expression(env, e))
-            .collect::<Vec<Expression>>();
+            .map(|e| eval_expression(env, e))
+            .collect::<Result<Vec<Expression>>>()?;
 
         Ok(evaluated[1..]
             .iter()
@@ -253,38 +374,46 @@ const OR: Expression = Expression::Builtin {
     },
 };
 
-const LET_MANY: Expression = Expression::Builtin {
+const LET_MANY: Expression = Expression::SpecialForm {
     name: "let*",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let variables = eval_expression(env, &list[0])?.as_list()?;
 
         for variable in variables {
             let var = variable.as_list()?;
-            let name = &var[0];
-            let value = &var[1];
+            let (Some(name), Some(value), None) = (var.first(), var.get(1), var.get(2)) else {
+                return Err(error(
+                    ErrorKind::Arity,
+                    format!(
+                        "let* expects (name value) pairs, got {}",
+                        variable.as_debug_string()
+                    ),
+                ));
+            };
             let evaluated = eval_expression(env, value)?;
             env.as_ref()
                 .borrow_mut()
                 .set_local(name.as_symbol_string()?, evaluated);
         }
 
-        let result = eval_expression(env, &list[1])?;
-
-        Ok(result)
+        Ok(Tail::Eval(env.clone(), list[1].clone()))
     },
 };
 
 const EVAL: Expression = Expression::Builtin {
     name: "eval",
+    arity: Arity::at_least(1),
     function: eval_list,
 };
 
-const EVAL_LOG: Expression = Expression::Builtin {
+const EVAL_LOG: Expression = Expression::SpecialForm {
     name: "eval-log",
+    arity: Arity::at_least(1),
     function: |env, list| {
         LAST_EVALUATION_COUNT.store(EVALUATION_COUNT.load(Ordering::SeqCst), Ordering::SeqCst);
 
-        let result = eval_expression(env, &Expression::List(list.to_vec()))?;
+        let result = eval_expression(env, &Expression::List(list.to_vec().into()))?;
 
         println!(
             "Evaluation count: {}",
@@ -293,17 +422,19 @@ const EVAL_LOG: Expression = Expression::Builtin {
 
         LAST_EVALUATION_COUNT.store(EVALUATION_COUNT.load(Ordering::SeqCst), Ordering::SeqCst);
 
-        Ok(result)
+        Ok(Tail::Value(result))
     },
 };
 
-const LAZY: Expression = Expression::Builtin {
+const LAZY: Expression = Expression::SpecialForm {
     name: "lazy",
-    function: |_env, list| Ok(list[0].clone()),
+    arity: Arity::exactly(1),
+    function: |_env, list| Ok(Tail::Value(list[0].clone())),
 };
 
-const TIME: Expression = Expression::Builtin {
+const TIME: Expression = Expression::SpecialForm {
     name: "time",
+    arity: Arity::exactly(1),
     function: |env, list| {
         let now = std::time::Instant::now();
 
@@ -311,18 +442,21 @@ const TIME: Expression = Expression::Builtin {
 
         println!("time: {} {} ms", &list[0], now.elapsed().as_millis());
 
-        result
+        result.map(Tail::Value)
     },
 };
 
 const CONCAT: Expression = Expression::Builtin {
     name: "concat",
+    arity: Arity::at_least(0),
     function: |env, list| {
         Ok(Expression::String(
             list.iter()
-                .flat_map(|l| eval_expression(env, l).map(|v| v.as_string()))
-                .filter_map(Result::ok)
-                .collect::<Vec<String>>()
+                .map(|l| match eval_expression(env, l)? {
+                    Expression::Char(c) => Ok(c.to_string()),
+                    v => v.as_string(),
+                })
+                .collect::<Result<Vec<String>>>()?
                 .join(""),
         ))
     },
@@ -330,6 +464,7 @@ const CONCAT: Expression = Expression::Builtin {
 
 const RANGE: Expression = Expression::Builtin {
     name: "range",
+    arity: Arity::exactly(2),
     function: |env, list| {
         Ok(Expression::List(
             (eval_expression(env, &list[0])?.as_i64()?
@@ -342,18 +477,18 @@ const RANGE: Expression = Expression::Builtin {
 
 const FOR: Expression = Expression::Builtin {
     name: "for",
+    arity: Arity::exactly(3),
     function: |env, list| {
         let iterator_name = &list[0];
         let iterable = eval_expression(env, &list[1])?;
         let func = eval_expression(env, &list[2])?;
 
         for i in iterable.as_list()? {
-            if let Expression::Builtin {
-                name: _,
-                function: actual,
+            if let Expression::SpecialForm {
+                function: actual, ..
             } = LET
             {
-                actual(env, &[iterator_name.clone(), i, func.clone()])?;
+                actual(env, &[iterator_name.clone(), i, func.clone()])?.finish()?;
             }
         }
 
@@ -363,6 +498,7 @@ const FOR: Expression = Expression::Builtin {
 
 const FOR_I: Expression = Expression::Builtin {
     name: "for-i",
+    arity: Arity::exactly(5),
     function: |env, list| {
         let iterator_name = &list[0];
         let iterator_value = &list[1];
@@ -371,9 +507,9 @@ const FOR_I: Expression = Expression::Builtin {
         let after = eval_expression(env, &list[4])?;
         let mut current = iterator_value.clone();
 
-        if let Expression::Builtin {
-            name: _,
+        if let Expression::SpecialForm {
             function: builtin_let,
+            ..
         } = LET
         {
             loop {
@@ -381,6 +517,7 @@ const FOR_I: Expression = Expression::Builtin {
                     env,
                     &[iterator_name.clone(), current.clone(), condition.clone()],
                 )?
+                .finish()?
                 .as_boolean()?
                 {
                     break;
@@ -389,9 +526,11 @@ const FOR_I: Expression = Expression::Builtin {
                 builtin_let(
                     env,
                     &[iterator_name.clone(), current.clone(), after.clone()],
-                )?;
+                )?
+                .finish()?;
 
-                current = builtin_let(env, &[iterator_name.clone(), current.clone(), f.clone()])?;
+                current = builtin_let(env, &[iterator_name.clone(), current.clone(), f.clone()])?
+                    .finish()?;
             }
         }
 
@@ -401,6 +540,7 @@ const FOR_I: Expression = Expression::Builtin {
 
 const MAP: Expression = Expression::Builtin {
     name: "map",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let func = eval_expression(env, &list[0])?;
 
@@ -408,14 +548,15 @@ const MAP: Expression = Expression::Builtin {
             eval_expression(env, &list[1])?
                 .as_list()?
                 .iter()
-                .flat_map(|x| eval_list(env, &[func.clone(), x.clone()]))
-                .collect::<Vec<Expression>>(),
+                .map(|x| eval_list(env, &[func.clone(), x.clone()]))
+                .collect::<Result<List>>()?,
         ))
     },
 };
 
 const FOLD: Expression = Expression::Builtin {
     name: "fold",
+    arity: Arity::exactly(3),
     function: |env, list| {
         let func = eval_expression(env, &list[0])?;
         let initial = eval_expression(env, &list[1])?;
@@ -431,27 +572,25 @@ const FOLD: Expression = Expression::Builtin {
 
 const FILTER: Expression = Expression::Builtin {
     name: "filter",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let func = eval_expression(env, &list[0])?;
 
-        Ok(Expression::List(
-            eval_expression(env, &list[1])?
-                .as_list()?
-                .iter()
-                .filter(|&x| {
-                    eval_list(env, &[func.clone(), x.clone()])
-                        .unwrap()
-                        .as_boolean()
-                        .unwrap()
-                })
-                .cloned()
-                .collect::<Vec<Expression>>(),
-        ))
+        let mut kept = vec![];
+
+        for x in eval_expression(env, &list[1])?.as_list()? {
+            if eval_list(env, &[func.clone(), x.clone()])?.as_boolean()? {
+                kept.push(x);
+            }
+        }
+
+        Ok(Expression::List(kept.into()))
     },
 };
 
 const PRINT: Expression = Expression::Builtin {
     name: "print",
+    arity: Arity::exactly(1),
     function: |env, list| {
         println!("{}", eval_expression(env, &list[0])?);
 
@@ -459,17 +598,61 @@ const PRINT: Expression = Expression::Builtin {
     },
 };
 
+/// `(to-string value)`, or `(to-string n radix)` to write an integer in
+/// another base, such as 16 for hexadecimal.
 const TO_STRING: Expression = Expression::Builtin {
     name: "to-string",
+    arity: Arity::between(1, 2),
     function: |env, list| {
-        Ok(Expression::String(
-            eval_expression(env, &list[0])?.to_string(),
-        ))
+        let value = eval_expression(env, &list[0])?;
+
+        let Some(radix) = list.get(1) else {
+            return Ok(Expression::String(value.to_string()));
+        };
+
+        let radix = eval_expression(env, radix)?.as_i64()?;
+        let n = match value.rational() {
+            Some(rational) if rational.is_integer() => rational.to_integer(),
+            _ => return Err(error(ErrorKind::Type, format!("Not an integer: {value}"))),
+        };
+
+        match u32::try_from(radix) {
+            Ok(radix @ 2..=36) => Ok(Expression::String(n.to_str_radix(radix))),
+            _ => Err(error(
+                ErrorKind::Arithmetic,
+                format!("Radix must be between 2 and 36, got {radix}"),
+            )),
+        }
+    },
+};
+
+const CHAR_TO_INTEGER: Expression = Expression::Builtin {
+    name: "char->integer",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let c = eval_expression(env, &list[0])?.as_char()?;
+
+        Ok(Expression::Integer(c as i64))
+    },
+};
+
+const INTEGER_TO_CHAR: Expression = Expression::Builtin {
+    name: "integer->char",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let code = eval_expression(env, &list[0])?.as_i64()?;
+
+        u32::try_from(code)
+            .ok()
+            .and_then(char::from_u32)
+            .map(Expression::Char)
+            .ok_or_else(|| error(ErrorKind::Type, format!("Not a character code: {code}")))
     },
 };
 
 const TO_SYMBOL: Expression = Expression::Builtin {
     name: "to-symbol",
+    arity: Arity::exactly(1),
     function: |env, list| {
         Ok(Expression::Symbol(
             eval_expression(env, &list[0])?.to_string(),
@@ -477,21 +660,25 @@ const TO_SYMBOL: Expression = Expression::Builtin {
     },
 };
 
-const AND_THEN: Expression = Expression::Builtin {
+const AND_THEN: Expression = Expression::SpecialForm {
     name: "and-then",
+    arity: Arity::at_least(0),
     function: |env, list| {
-        let mut last = Expression::Nil;
+        let Some((last, init)) = list.split_last() else {
+            return Ok(Tail::Value(Expression::Nil));
+        };
 
-        for expression in list {
-            last = eval_expression(env, expression)?;
+        for expression in init {
+            eval_expression(env, expression)?;
         }
 
-        Ok(last)
+        Ok(Tail::Eval(env.clone(), last.clone()))
     },
 };
 
 const EXISTS: Expression = Expression::Builtin {
     name: "exists",
+    arity: Arity::exactly(1),
     function: |env, list| {
         let evaluated = eval_expression(env, &list[0])?.as_symbol_string()?;
 
@@ -501,6 +688,7 @@ const EXISTS: Expression = Expression::Builtin {
 
 const CONCAT_SYMBOL: Expression = Expression::Builtin {
     name: "concat-symbol",
+    arity: Arity::at_least(0),
     function: |_env, list| {
         Ok(Expression::Symbol(
             list.iter()
@@ -513,6 +701,7 @@ const CONCAT_SYMBOL: Expression = Expression::Builtin {
 
 const WEB_SERVER: Expression = Expression::Builtin {
     name: "web-server",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let port = eval_expression(env, &list[0])?;
         let routes = eval_expression(env, &list[1])?;
@@ -528,7 +717,9 @@ const WEB_SERVER: Expression = Expression::Builtin {
             );
         }
 
-        let server = tiny_http::Server::http(format!("127.0.0.1:{}", port.as_i64()?)).unwrap();
+        let address = format!("127.0.0.1:{}", port.as_i64()?);
+        let server = tiny_http::Server::http(&address)
+            .map_err(|e| eyre!("Can't start web server on {address}: {e}"))?;
 
         for request in server.incoming_requests() {
             let response =
@@ -552,28 +743,31 @@ const WEB_SERVER: Expression = Expression::Builtin {
 
 const APPEND: Expression = Expression::Builtin {
     name: "append",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let mut new_list = eval_expression(env, &list[1])?.as_list()?;
 
         new_list.push(eval_expression(env, &list[0])?);
 
-        Ok(Expression::List(new_list))
+        Ok(Expression::List(new_list.into()))
     },
 };
 
 const PREPEND: Expression = Expression::Builtin {
     name: "prepend",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let mut new_list = vec![eval_expression(env, &list[0])?];
 
         new_list.extend(eval_expression(env, &list[1])?.as_list()?);
 
-        Ok(Expression::List(new_list))
+        Ok(Expression::List(new_list.into()))
     },
 };
 
 const ROUND: Expression = Expression::Builtin {
     name: "round",
+    arity: Arity::exactly(1),
     function: |env, list| {
         let evaluated = eval_expression(env, &list[0])?;
 
@@ -581,52 +775,142 @@ const ROUND: Expression = Expression::Builtin {
     },
 };
 
+const NUMERATOR: Expression = Expression::Builtin {
+    name: "numerator",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let evaluated = eval_expression(env, &list[0])?;
+        let rational = evaluated
+            .rational()
+            .ok_or_else(|| error(ErrorKind::Type, format!("Not exact: {evaluated}")))?;
+
+        Ok(rational.numer().clone().into())
+    },
+};
+
+const DENOMINATOR: Expression = Expression::Builtin {
+    name: "denominator",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let evaluated = eval_expression(env, &list[0])?;
+        let rational = evaluated
+            .rational()
+            .ok_or_else(|| error(ErrorKind::Type, format!("Not exact: {evaluated}")))?;
+
+        Ok(rational.denom().clone().into())
+    },
+};
+
+/// `(exact->inexact n)`, the float nearest to `n`.
+const EXACT_TO_INEXACT: Expression = Expression::Builtin {
+    name: "exact->inexact",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let evaluated = eval_expression(env, &list[0])?;
+
+        evaluated
+            .number()
+            .map(Expression::Float)
+            .ok_or_else(|| error(ErrorKind::Type, format!("Not a number: {evaluated}")))
+    },
+};
+
+/// The range `start..end` of a sequence of `length` items, checked the way
+/// `slice` always has.
+fn slice_range(start: i64, end: i64, length: usize) -> Result<std::ops::Range<usize>> {
+    if start < 0 {
+        return Err(eyre!("Index below zero: {start}"));
+    }
+
+    if start
+        .checked_add(end)
+        .is_none_or(|total| total > length as i64)
+    {
+        return Err(eyre!("Out of bounds: {start} + {end} > {length}"));
+    }
+
+    if end < start {
+        return Err(eyre!("Slice ends before it starts: {start} to {end}"));
+    }
+
+    Ok(start as usize..end as usize)
+}
+
+/// `(nth index list)`, or the character at `index` of a string.
 const NTH: Expression = Expression::Builtin {
     name: "nth",
+    arity: Arity::exactly(2),
     function: |env, list| {
-        let index = eval_expression(env, &list[0])?.as_i64()? as usize;
-        let l = eval_expression(env, &list[1])?.as_list()?;
-
-        Ok(l[index].clone())
+        let index = eval_expression(env, &list[0])?.as_i64()?;
+
+        match eval_expression(env, &list[1])? {
+            Expression::String(s) => usize::try_from(index)
+                .ok()
+                .and_then(|i| s.chars().nth(i))
+                .map(Expression::Char)
+                .ok_or_else(|| {
+                    eyre!(
+                        "Out of bounds: {index} of a string of length {}",
+                        s.chars().count()
+                    )
+                }),
+            evaluated => {
+                let l = evaluated.as_list()?;
+
+                usize::try_from(index)
+                    .ok()
+                    .and_then(|i| l.get(i).cloned())
+                    .ok_or_else(|| eyre!("Out of bounds: {index} of a list of length {}", l.len()))
+            }
+        }
     },
 };
 
 const SLICE: Expression = Expression::Builtin {
     name: "slice",
+    arity: Arity::exactly(3),
     function: |env, list| {
         let start = eval_expression(env, &list[0])?.as_i64()?;
         let end = eval_expression(env, &list[1])?.as_i64()?;
-        let l = eval_expression(env, &list[2])?.as_list()?;
 
-        if start < 0 {
-            return Err(eyre!("Index below zero: {start}"));
-        }
+        match eval_expression(env, &list[2])? {
+            Expression::String(s) => {
+                let chars = s.chars().collect::<Vec<char>>();
+                let range = slice_range(start, end, chars.len())?;
 
-        if start + end > l.len() as i64 {
-            return Err(eyre!("Out of bounds: {start} + {end} > {}", l.len()));
-        }
+                Ok(Expression::String(chars[range].iter().collect()))
+            }
+            evaluated => {
+                let l = evaluated.as_list()?;
+                let range = slice_range(start, end, l.len())?;
 
-        Ok(Expression::List(l[start as usize..end as usize].to_vec()))
+                Ok(Expression::List(l[range].to_vec().into()))
+            }
+        }
     },
 };
 
 const REVERSE: Expression = Expression::Builtin {
     name: "reverse",
-    function: |env, list| {
-        let l = eval_expression(env, &list[0])?.as_list()?;
-
-        Ok(Expression::List(l.iter().rev().cloned().collect()))
+    arity: Arity::exactly(1),
+    function: |env, list| match eval_expression(env, &list[0])? {
+        Expression::String(s) => Ok(Expression::String(s.chars().rev().collect())),
+        evaluated => Ok(Expression::List(
+            evaluated.as_list()?.into_iter().rev().collect(),
+        )),
     },
 };
 
+/// The number of items in a list, or of characters in a string.
 const LENGTH: Expression = Expression::Builtin {
     name: "length",
+    arity: Arity::exactly(1),
     function: |env, list| {
         let evaluated = eval_expression(env, &list[0])?;
 
         Ok(Expression::Integer(match evaluated {
             Expression::List(l) => l.len() as i64,
-            Expression::String(s) => s.len() as i64,
+            Expression::String(s) => s.chars().count() as i64,
             _ => Err(eyre!("Doesn't have length: {evaluated}"))?,
         }))
     },
@@ -634,6 +918,7 @@ const LENGTH: Expression = Expression::Builtin {
 
 const TANGLE: Expression = Expression::Builtin {
     name: "tangle",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let with = eval_expression(env, &list[0])?;
         let l = eval_expression(env, &list[1])?.as_list()?;
@@ -647,12 +932,13 @@ const TANGLE: Expression = Expression::Builtin {
             }
         }
 
-        Ok(Expression::List(new_list))
+        Ok(Expression::List(new_list.into()))
     },
 };
 
 const TYPE: Expression = Expression::Builtin {
     name: "type",
+    arity: Arity::exactly(1),
     function: |env, list| {
         let evaluated = eval_expression(env, &list[0])?;
 
@@ -662,6 +948,7 @@ const TYPE: Expression = Expression::Builtin {
 
 const READ: Expression = Expression::Builtin {
     name: "read",
+    arity: Arity::exactly(1),
     function: |env, list| {
         let file_name = eval_expression(env, &list[0])?.as_string()?;
 
@@ -673,6 +960,7 @@ const READ: Expression = Expression::Builtin {
 
 const WRITE: Expression = Expression::Builtin {
     name: "write",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let file_name = eval_expression(env, &list[0])?;
         let content = eval_expression(env, &list[1])?;
@@ -685,21 +973,28 @@ const WRITE: Expression = Expression::Builtin {
 
 const SPLIT: Expression = Expression::Builtin {
     name: "split",
+    arity: Arity::exactly(2),
     function: |env, list| {
-        let by = eval_expression(env, &list[0])?.as_string()?;
+        let by = eval_expression(env, &list[0])?;
         let content = eval_expression(env, &list[1])?.as_string()?;
 
+        let parts: Vec<String> = match by {
+            Expression::Char(c) => content.split(c).map(str::to_string).collect(),
+            by => match by.as_string()?.as_str() {
+                "" => content.chars().map(String::from).collect(),
+                by => content.split(by).map(str::to_string).collect(),
+            },
+        };
+
         Ok(Expression::List(
-            content
-                .split(&by)
-                .map(|s| Expression::String(s.to_string()))
-                .collect(),
+            parts.into_iter().map(Expression::String).collect(),
         ))
     },
 };
 
 const ZIP: Expression = Expression::Builtin {
     name: "zip",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let a = eval_expression(env, &list[0])?.as_list()?;
         let b = eval_expression(env, &list[1])?.as_list()?;
@@ -708,7 +1003,7 @@ const ZIP: Expression = Expression::Builtin {
             a.iter()
                 .cloned()
                 .zip(b)
-                .map(|(x, y)| Expression::List(vec![x, y]))
+                .map(|(x, y)| Expression::List(vec![x, y].into()))
                 .collect(),
         ))
     },
@@ -716,6 +1011,7 @@ const ZIP: Expression = Expression::Builtin {
 
 const ZIP_WITH: Expression = Expression::Builtin {
     name: "zip-with",
+    arity: Arity::exactly(3),
     function: |env, list| {
         let with = eval_expression(env, &list[0])?;
         let a = eval_expression(env, &list[1])?.as_list()?;
@@ -724,14 +1020,15 @@ const ZIP_WITH: Expression = Expression::Builtin {
         Ok(Expression::List(
             a.iter()
                 .zip(b)
-                .flat_map(|(x, y)| eval_list(env, &[with.clone(), x.clone(), y.clone()]))
-                .collect(),
+                .map(|(x, y)| eval_list(env, &[with.clone(), x.clone(), y.clone()]))
+                .collect::<Result<List>>()?,
         ))
     },
 };
 
 const IMPORT: Expression = Expression::Builtin {
     name: "import",
+    arity: Arity::exactly(1),
     function: |env, list| {
         let path = &list[0].as_string()?;
 
@@ -741,21 +1038,17 @@ const IMPORT: Expression = Expression::Builtin {
             std_lib(),
         ))))));
 
-        run(&mut module_env, &content)?;
+        run_source(&mut module_env, path, &content)?;
 
-        for (symbol, value) in module_env
-            .borrow_mut()
+        let exported = module_env
+            .borrow()
             .get("__EXPORTED")
-            .unwrap()
-            .as_table()?
-        {
-            if let Expression::Table(ref mut table) = env
-                .borrow_mut()
-                .get_mut_local("__IMPORTED".to_string())
-                .unwrap()
-            {
-                table.insert(symbol, value);
-            }
+            .ok_or_else(|| eyre!("Module {path} has no exports"))?
+            .as_table()?;
+
+        match env.borrow_mut().get_mut_local("__IMPORTED".to_string()) {
+            Some(Expression::Table(table)) => table.extend(exported),
+            _ => return Err(eyre!("Can't import outside of a module")),
         }
 
         Ok(Expression::Nil)
@@ -764,55 +1057,232 @@ const IMPORT: Expression = Expression::Builtin {
 
 const EXPORT: Expression = Expression::Builtin {
     name: "export",
+    arity: Arity::exactly(1),
     function: |env, list| {
-        let symbol = &list[0];
+        let symbol = list[0].as_symbol_string()?;
 
-        let value = env.borrow_mut().get(&symbol.as_symbol_string()?).unwrap();
+        let value = env
+            .borrow()
+            .get(&symbol)
+            .ok_or_else(|| eyre!("Can't export undefined symbol: {symbol}"))?;
 
-        if let Expression::Table(ref mut table) = env
-            .borrow_mut()
-            .get_mut_local("__EXPORTED".to_string())
-            .unwrap()
-        {
-            table.insert(symbol.as_symbol_string()?, value);
-        }
+        match env.borrow_mut().get_mut_local("__EXPORTED".to_string()) {
+            Some(Expression::Table(table)) => {
+                table.insert(Key::Symbol(symbol), value);
 
-        Ok(Expression::Nil)
+                Ok(Expression::Nil)
+            }
+            _ => Err(eyre!("Can't export from outside of a module")),
+        }
     },
 };
 
 const MODULE: Expression = Expression::Builtin {
     name: "module",
+    arity: Arity::exactly(0),
     function: |env, _list| {
-        Ok(Expression::Table(HashMap::from([
+        Ok(Expression::Table(Table::from([
             (
-                "imported".to_string(),
-                env.borrow().get("__IMPORTED").unwrap(),
+                Key::from("imported"),
+                env.borrow().get("__IMPORTED").unwrap_or(Expression::Nil),
             ),
             (
-                "exported".to_string(),
-                env.borrow().get("__EXPORTED").unwrap(),
+                Key::from("exported"),
+                env.borrow().get("__EXPORTED").unwrap_or(Expression::Nil),
             ),
         ])))
     },
 };
 
-const QUOTE: Expression = Expression::Builtin {
+const QUOTE: Expression = Expression::SpecialForm {
     name: "quote",
-    function: |_env, list| Ok(list[0].clone()),
+    arity: Arity::exactly(1),
+    function: |_env, list| Ok(Tail::Value(list[0].clone())),
+};
+
+const QUASIQUOTE: Expression = Expression::SpecialForm {
+    name: "quasiquote",
+    arity: Arity::exactly(1),
+    function: |env, list| quasiquote(env, &list[0], 1).map(Tail::Value),
 };
 
+/// Copies `template`, evaluating the `unquote` and `unquote-splicing` forms
+/// that belong to this quasiquote level and leaving nested levels intact.
+fn quasiquote(
+    env: &mut Rc<RefCell<Env>>,
+    template: &Expression,
+    depth: usize,
+) -> Result<Expression> {
+    let Expression::List(items) = template else {
+        return Ok(template.clone());
+    };
+
+    if let [Expression::Symbol(head), inner] = items.as_slice() {
+        match head.as_str() {
+            "unquote" if depth == 1 => return eval_expression(env, inner),
+            "unquote-splicing" if depth == 1 => {
+                return Err(eyre!("unquote-splicing outside of a list: {template}"))
+            }
+            "unquote" | "unquote-splicing" => {
+                return Ok(Expression::List(
+                    vec![items[0].clone(), quasiquote(env, inner, depth - 1)?].into(),
+                ))
+            }
+            "quasiquote" => {
+                return Ok(Expression::List(
+                    vec![items[0].clone(), quasiquote(env, inner, depth + 1)?].into(),
+                ))
+            }
+            _ => {}
+        }
+    }
+
+    let mut result = vec![];
+
+    for item in items {
+        match item {
+            Expression::List(l)
+                if depth == 1
+                    && l.len() == 2
+                    && l[0] == Expression::Symbol("unquote-splicing".to_string()) =>
+            {
+                result.extend(eval_expression(env, &l[1])?.as_list()?);
+            }
+            _ => result.push(quasiquote(env, item, depth)?),
+        }
+    }
+
+    Ok(Expression::List(result.into()))
+}
+
+const THROW: Expression = Expression::Builtin {
+    name: "throw",
+    arity: Arity::exactly(1),
+    function: |env, list| Err(throw(eval_expression(env, &list[0])?)),
+};
+
+/// `(try body... (catch e handler...) (finally cleanup...))`
+///
+/// Both clauses are optional. The handler sees the error as a table of
+/// `message`, `kind`, `trace` and the thrown `value`.
+const TRY: Expression = Expression::SpecialForm {
+    name: "try",
+    arity: Arity::at_least(1),
+    function: |env, list| {
+        let catch = clause(list, "catch")?;
+        let finally = clause(list, "finally")?;
+
+        let body = list
+            .iter()
+            .filter(|form| !is_clause(form, "catch") && !is_clause(form, "finally"))
+            .try_fold(Expression::Nil, |_, form| eval_expression(env, form));
+
+        let result = match (body, catch.as_ref().map(|catch| &catch[..])) {
+            (Err(report), Some([_, name, handler @ ..])) => {
+                let mut handler_env = Rc::new(RefCell::new(Env::new(Some(env.clone()))));
+
+                handler_env
+                    .borrow_mut()
+                    .set_local(name.as_symbol_string()?, caught(&report));
+
+                handler.iter().try_fold(Expression::Nil, |_, form| {
+                    eval_expression(&mut handler_env, form)
+                })
+            }
+            (body, _) => body,
+        };
+
+        if let Some(cleanup) = finally {
+            for form in &cleanup[1..] {
+                eval_expression(env, form)?;
+            }
+        }
+
+        result.map(Tail::Value)
+    },
+};
+
+/// The `name` clause of a `try`, checked before the body runs so a malformed
+/// one is reported instead of hiding the error it was meant to handle.
+fn clause(list: &[Expression], name: &str) -> Result<Option<List>> {
+    let mut clauses = list.iter().filter_map(|form| match form {
+        Expression::List(clause) if is_clause(form, name) => Some(clause),
+        _ => None,
+    });
+
+    let Some(clause) = clauses.next() else {
+        return Ok(None);
+    };
+
+    if let Some(again) = clauses.next() {
+        return Err(at_clause(
+            again,
+            error(
+                ErrorKind::Arity,
+                format!("try takes at most one {name} clause"),
+            ),
+        ));
+    }
+
+    if name == "catch" {
+        match clause.get(1) {
+            Some(Expression::Symbol(_)) => {}
+            Some(other) => {
+                return Err(at_clause(
+                    clause,
+                    error(
+                        ErrorKind::Type,
+                        format!("catch names the error with a symbol, got {other}"),
+                    ),
+                ))
+            }
+            None => {
+                return Err(at_clause(
+                    clause,
+                    error(ErrorKind::Arity, "catch expects a name for the error"),
+                ))
+            }
+        }
+    }
+
+    Ok(Some(clause.clone()))
+}
+
+/// Points `error` at `clause`, when it was read from a source.
+fn at_clause(clause: &List, error: Report) -> Report {
+    match clause.location() {
+        Some(location) => source::located(error, location.excerpt()),
+        None => error,
+    }
+}
+
+/// Whether `form` is a list headed by the symbol `name`.
+fn is_clause(form: &Expression, name: &str) -> bool {
+    matches!(form, Expression::List(l) if l.first() == Some(&Expression::Symbol(name.into())))
+}
+
+/// `(env)`, the values defined in the current scope, ordered by name.
 const ENV: Expression = Expression::Builtin {
     name: "env",
+    arity: Arity::exactly(0),
     function: |env, _list| {
+        let env = env.borrow();
+        let mut names = env.local.keys().collect::<Vec<&String>>();
+
+        names.sort();
+
         Ok(Expression::List(
-            env.borrow().local.values().cloned().collect(),
+            names
+                .into_iter()
+                .map(|name| env.local[name].clone())
+                .collect(),
         ))
     },
 };
 
 const APPLY: Expression = Expression::Builtin {
     name: "apply",
+    arity: Arity::exactly(2),
     function: |env, list| {
         let f = eval_expression(env, &list[0])?;
         let args = eval_expression(env, &list[1])?.as_list()?;
@@ -824,6 +1294,7 @@ const APPLY: Expression = Expression::Builtin {
 
 const INSPECT: Expression = Expression::Builtin {
     name: "inspect",
+    arity: Arity::exactly(1),
     function: |env, list| {
         let value = eval_expression(env, &list[0])?;
 
@@ -867,8 +1338,13 @@ pub fn std_lib() -> Env {
         FILTER,
         PRINT,
         ROUND,
+        NUMERATOR,
+        DENOMINATOR,
+        EXACT_TO_INEXACT,
         WEB_SERVER,
         TO_STRING,
+        CHAR_TO_INTEGER,
+        INTEGER_TO_CHAR,
         TO_SYMBOL,
         AND_THEN,
         EXISTS,
@@ -890,9 +1366,55 @@ pub fn std_lib() -> Env {
         EXPORT,
         MODULE,
         QUOTE,
+        QUASIQUOTE,
+        DEFMACRO,
+        MACROEXPAND,
+        GENSYM,
+        THROW,
+        TRY,
         ENV,
         APPLY,
         INSPECT,
+        json::PARSE,
+        json::STRINGIFY,
+        table::TABLE,
+        table::GET,
+        table::GET_IN,
+        table::ASSOC,
+        table::DISSOC,
+        table::UPDATE,
+        table::KEYS,
+        table::VALUES,
+        table::ENTRIES,
+        table::MERGE,
+        table::HAS_KEY,
+        grapheme::SPLIT,
+        grapheme::LENGTH,
+        grapheme::NTH,
+        grapheme::SLICE,
+        grapheme::REVERSE,
+        string::TRIM,
+        string::UPPER,
+        string::LOWER,
+        string::STARTS_WITH,
+        string::ENDS_WITH,
+        string::CONTAINS,
+        string::INDEX_OF,
+        string::REPLACE,
+        string::PAD_LEFT,
+        string::PAD_RIGHT,
+        string::REPEAT,
+        string::JOIN,
+        string::LINES,
+        string::CHARS,
+        string::PARSE_INT,
+        string::PARSE_FLOAT,
+        string::FORMAT,
+        regex::MATCH,
+        regex::FIND_ALL,
+        regex::CAPTURES,
+        regex::REPLACE,
+        regex::SPLIT,
     ];
 
     /*
@@ -913,7 +1435,7 @@ pub fn std_lib() -> Env {
 
     let mut env = Env {
         local: HashMap::from_iter(std.iter().map(|pair| {
-            if let Expression::Builtin { name, function: _ } = pair {
+            if let Expression::Builtin { name, .. } | Expression::SpecialForm { name, .. } = pair {
                 (name.to_string(), pair.clone())
             } else {
                 unreachable!()
@@ -922,7 +1444,7 @@ pub fn std_lib() -> Env {
         parent: None,
     };
 
-    env.set_global("t".to_string(), crate::expression::TRUE.clone());
+    env.set_global("t".to_string(), Expression::Bool(true));
 
     env
 }
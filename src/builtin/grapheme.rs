@@ -0,0 +1,80 @@
+use color_eyre::eyre::eyre;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    eval::eval_expression,
+    expression::{Arity, Expression},
+};
+
+use super::slice_range;
+
+fn graphemes(s: &str) -> Vec<&str> {
+    s.graphemes(true).collect()
+}
+
+/// `(grapheme/split s)`, the user-perceived characters of `s` as strings, so
+/// `"e\u{301}"` is one grapheme where `length` counts two characters.
+pub const SPLIT: Expression = Expression::Builtin {
+    name: "grapheme/split",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let s = eval_expression(env, &list[0])?.as_string()?;
+
+        Ok(Expression::List(
+            s.graphemes(true)
+                .map(|g| Expression::String(g.to_string()))
+                .collect(),
+        ))
+    },
+};
+
+pub const LENGTH: Expression = Expression::Builtin {
+    name: "grapheme/length",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let s = eval_expression(env, &list[0])?.as_string()?;
+
+        Ok(Expression::Integer(graphemes(&s).len() as i64))
+    },
+};
+
+pub const NTH: Expression = Expression::Builtin {
+    name: "grapheme/nth",
+    arity: Arity::exactly(2),
+    function: |env, list| {
+        let index = eval_expression(env, &list[0])?.as_i64()?;
+        let s = eval_expression(env, &list[1])?.as_string()?;
+        let g = graphemes(&s);
+
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| g.get(i))
+            .map(|g| Expression::String(g.to_string()))
+            .ok_or_else(|| eyre!("Out of bounds: {index} of a string of length {}", g.len()))
+    },
+};
+
+pub const SLICE: Expression = Expression::Builtin {
+    name: "grapheme/slice",
+    arity: Arity::exactly(3),
+    function: |env, list| {
+        let start = eval_expression(env, &list[0])?.as_i64()?;
+        let end = eval_expression(env, &list[1])?.as_i64()?;
+        let s = eval_expression(env, &list[2])?.as_string()?;
+        let g = graphemes(&s);
+
+        Ok(Expression::String(
+            g[slice_range(start, end, g.len())?].concat(),
+        ))
+    },
+};
+
+pub const REVERSE: Expression = Expression::Builtin {
+    name: "grapheme/reverse",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let s = eval_expression(env, &list[0])?.as_string()?;
+
+        Ok(Expression::String(s.graphemes(true).rev().collect()))
+    },
+};
@@ -0,0 +1,66 @@
+use color_eyre::Result;
+use num_traits::ToPrimitive;
+
+use crate::{
+    error::{error, ErrorKind},
+    eval::eval_expression,
+    expression::{Arity, Expression},
+};
+
+/// `(json/parse text)`, objects becoming tables, arrays lists and `null` nil.
+pub const PARSE: Expression = Expression::Builtin {
+    name: "json/parse",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let text = eval_expression(env, &list[0])?.as_string()?;
+
+        serde_json::from_str(&text)
+            .map_err(|e| error(ErrorKind::Parse, format!("Invalid JSON: {e}")))
+    },
+};
+
+/// Fails on numbers JSON has no way to write, which serde_json would
+/// otherwise quietly turn into `null`.
+fn check_finite(value: &Expression) -> Result<()> {
+    let finite = match value {
+        Expression::Float(f) => f.is_finite(),
+        Expression::Rational(r) => r.to_f64().is_some_and(f64::is_finite),
+        Expression::List(list) => return list.iter().try_for_each(check_finite),
+        Expression::Table(table) => return table.values().try_for_each(check_finite),
+        _ => true,
+    };
+
+    if finite {
+        Ok(())
+    } else {
+        Err(error(
+            ErrorKind::Type,
+            format!("Can't encode JSON: {value} is not a finite number"),
+        ))
+    }
+}
+
+/// `(json/stringify value)`, or `(json/stringify value true)` to pretty print.
+pub const STRINGIFY: Expression = Expression::Builtin {
+    name: "json/stringify",
+    arity: Arity::between(1, 2),
+    function: |env, list| {
+        let value = eval_expression(env, &list[0])?;
+        let pretty = match list.get(1) {
+            Some(pretty) => eval_expression(env, pretty)?.as_boolean()?,
+            None => false,
+        };
+
+        check_finite(&value)?;
+
+        let text = if pretty {
+            serde_json::to_string_pretty(&value)
+        } else {
+            serde_json::to_string(&value)
+        };
+
+        Ok(Expression::String(text.map_err(|e| {
+            error(ErrorKind::Type, format!("Can't encode JSON: {e}"))
+        })?))
+    },
+};
@@ -0,0 +1,155 @@
+use ::regex::Regex;
+use color_eyre::Result;
+use hashbrown::HashMap;
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    env::Env,
+    error::{error, ErrorKind},
+    eval::{eval_expression, eval_list, quoted},
+    expression::{Arity, Expression, Key, Table},
+};
+
+/// How many compiled patterns are kept before the cache starts over.
+const CACHE_SIZE: usize = 256;
+
+thread_local! {
+    static CACHE: RefCell<HashMap<String, Regex>> = RefCell::new(HashMap::new());
+}
+
+/// Compiles `pattern`, or reuses it from an earlier call, so a pattern in a
+/// loop is only compiled once.
+fn compile(pattern: &str) -> Result<Regex> {
+    if let Some(regex) = CACHE.with(|cache| cache.borrow().get(pattern).cloned()) {
+        return Ok(regex);
+    }
+
+    let regex =
+        Regex::new(pattern).map_err(|e| error(ErrorKind::Parse, format!("Invalid regex: {e}")))?;
+
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+
+        if cache.len() >= CACHE_SIZE {
+            cache.clear();
+        }
+
+        cache.insert(pattern.to_string(), regex.clone());
+    });
+
+    Ok(regex)
+}
+
+/// The compiled pattern and the string of a `(regex/... pattern ... s)` call.
+fn arguments(env: &mut Rc<RefCell<Env>>, list: &[Expression]) -> Result<(Regex, String)> {
+    let regex = compile(&eval_expression(env, &list[0])?.as_string()?)?;
+    let s = eval_expression(env, &list[list.len() - 1])?.as_string()?;
+
+    Ok((regex, s))
+}
+
+/// `(regex/match pattern s)`, the first match of `pattern` in `s`, or `nil`.
+pub const MATCH: Expression = Expression::Builtin {
+    name: "regex/match",
+    arity: Arity::exactly(2),
+    function: |env, list| {
+        let (regex, s) = arguments(env, list)?;
+
+        Ok(match regex.find(&s) {
+            Some(m) => Expression::String(m.as_str().to_string()),
+            None => Expression::Nil,
+        })
+    },
+};
+
+pub const FIND_ALL: Expression = Expression::Builtin {
+    name: "regex/find-all",
+    arity: Arity::exactly(2),
+    function: |env, list| {
+        let (regex, s) = arguments(env, list)?;
+
+        Ok(Expression::List(
+            regex
+                .find_iter(&s)
+                .map(|m| Expression::String(m.as_str().to_string()))
+                .collect(),
+        ))
+    },
+};
+
+/// `(regex/captures pattern s)`, a table from the names of the groups in
+/// `pattern` to what they matched, `nil` for a group that took no part, or
+/// `nil` altogether if there is no match.
+pub const CAPTURES: Expression = Expression::Builtin {
+    name: "regex/captures",
+    arity: Arity::exactly(2),
+    function: |env, list| {
+        let (regex, s) = arguments(env, list)?;
+
+        let Some(captures) = regex.captures(&s) else {
+            return Ok(Expression::Nil);
+        };
+
+        let mut table = Table::new();
+
+        for name in regex.capture_names().flatten() {
+            let value = match captures.name(name) {
+                Some(m) => Expression::String(m.as_str().to_string()),
+                None => Expression::Nil,
+            };
+
+            table.insert(Key::from(name), value);
+        }
+
+        Ok(Expression::Table(table))
+    },
+};
+
+/// `(regex/replace pattern replacement s)`, replacing every match. The
+/// replacement is either a string, where `$1` or `$name` refer to groups,
+/// or a function given each matched string.
+pub const REPLACE: Expression = Expression::Builtin {
+    name: "regex/replace",
+    arity: Arity::exactly(3),
+    function: |env, list| {
+        let (regex, s) = arguments(env, list)?;
+        let replacement = eval_expression(env, &list[1])?;
+
+        if let Expression::String(template) = replacement {
+            return Ok(Expression::String(
+                regex.replace_all(&s, template.as_str()).into_owned(),
+            ));
+        }
+
+        let mut replaced = String::with_capacity(s.len());
+        let mut last = 0;
+
+        for m in regex.find_iter(&s) {
+            let text = Expression::String(m.as_str().to_string());
+            let new = eval_list(env, &[replacement.clone(), quoted(text)])?.as_string()?;
+
+            replaced.push_str(&s[last..m.start()]);
+            replaced.push_str(&new);
+            last = m.end();
+        }
+
+        replaced.push_str(&s[last..]);
+
+        Ok(Expression::String(replaced))
+    },
+};
+
+pub const SPLIT: Expression = Expression::Builtin {
+    name: "regex/split",
+    arity: Arity::exactly(2),
+    function: |env, list| {
+        let (regex, s) = arguments(env, list)?;
+
+        Ok(Expression::List(
+            regex
+                .split(&s)
+                .map(|part| Expression::String(part.to_string()))
+                .collect(),
+        ))
+    },
+};
@@ -0,0 +1,327 @@
+use color_eyre::Result;
+use num_bigint::BigInt;
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    env::Env,
+    error::{error, ErrorKind},
+    eval::eval_expression,
+    expression::{Arity, Expression},
+};
+
+/// `value` as it reads inside text: strings and characters as they are,
+/// anything else as it prints.
+fn text(value: &Expression) -> String {
+    match value {
+        Expression::String(s) => s.clone(),
+        Expression::Char(c) => c.to_string(),
+        value => value.as_debug_string(),
+    }
+}
+
+fn string_arg(env: &mut Rc<RefCell<Env>>, expression: &Expression) -> Result<String> {
+    eval_expression(env, expression)?.as_string()
+}
+
+/// The longest string, in bytes, that repeating or padding may build.
+const MAX_LENGTH: usize = 1 << 30;
+
+/// The size of `count` copies of `bytes` bytes, if that is a reasonable
+/// amount to allocate.
+fn repeated_size(count: usize, bytes: usize) -> Result<usize> {
+    count
+        .checked_mul(bytes)
+        .filter(|&size| size <= MAX_LENGTH)
+        .ok_or_else(|| {
+            error(
+                ErrorKind::Arithmetic,
+                format!("String too long: {count} times {bytes} bytes"),
+            )
+        })
+}
+
+pub const TRIM: Expression = Expression::Builtin {
+    name: "string/trim",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        Ok(Expression::String(
+            string_arg(env, &list[0])?.trim().to_string(),
+        ))
+    },
+};
+
+pub const UPPER: Expression = Expression::Builtin {
+    name: "string/upper",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        Ok(Expression::String(
+            string_arg(env, &list[0])?.to_uppercase(),
+        ))
+    },
+};
+
+pub const LOWER: Expression = Expression::Builtin {
+    name: "string/lower",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        Ok(Expression::String(
+            string_arg(env, &list[0])?.to_lowercase(),
+        ))
+    },
+};
+
+/// `(string/starts-with prefix s)`
+pub const STARTS_WITH: Expression = Expression::Builtin {
+    name: "string/starts-with",
+    arity: Arity::exactly(2),
+    function: |env, list| {
+        let prefix = string_arg(env, &list[0])?;
+
+        Ok(string_arg(env, &list[1])?.starts_with(&prefix).into())
+    },
+};
+
+/// `(string/ends-with suffix s)`
+pub const ENDS_WITH: Expression = Expression::Builtin {
+    name: "string/ends-with",
+    arity: Arity::exactly(2),
+    function: |env, list| {
+        let suffix = string_arg(env, &list[0])?;
+
+        Ok(string_arg(env, &list[1])?.ends_with(&suffix).into())
+    },
+};
+
+/// `(string/contains needle s)`
+pub const CONTAINS: Expression = Expression::Builtin {
+    name: "string/contains",
+    arity: Arity::exactly(2),
+    function: |env, list| {
+        let needle = string_arg(env, &list[0])?;
+
+        Ok(string_arg(env, &list[1])?.contains(&needle).into())
+    },
+};
+
+/// `(string/index-of needle s)`, the character index of the first `needle`
+/// in `s`, or `nil`.
+pub const INDEX_OF: Expression = Expression::Builtin {
+    name: "string/index-of",
+    arity: Arity::exactly(2),
+    function: |env, list| {
+        let needle = string_arg(env, &list[0])?;
+        let s = string_arg(env, &list[1])?;
+
+        Ok(match s.find(&needle) {
+            Some(byte) => Expression::Integer(s[..byte].chars().count() as i64),
+            None => Expression::Nil,
+        })
+    },
+};
+
+/// `(string/replace from to s)`, replacing every `from`.
+pub const REPLACE: Expression = Expression::Builtin {
+    name: "string/replace",
+    arity: Arity::exactly(3),
+    function: |env, list| {
+        let from = string_arg(env, &list[0])?;
+        let to = string_arg(env, &list[1])?;
+
+        Ok(Expression::String(
+            string_arg(env, &list[2])?.replace(&from, &to),
+        ))
+    },
+};
+
+/// The string to pad and the padding needed to bring it up to `width`
+/// characters, from `pad` or spaces.
+fn padding(env: &mut Rc<RefCell<Env>>, list: &[Expression]) -> Result<(String, String)> {
+    let width = eval_expression(env, &list[0])?.as_i64()?;
+    let pad = match list {
+        [_, pad, _] => eval_expression(env, pad)?.as_char()?,
+        _ => ' ',
+    };
+    let s = string_arg(env, &list[list.len() - 1])?;
+
+    let missing = width.saturating_sub(s.chars().count() as i64).max(0) as usize;
+
+    repeated_size(missing, pad.len_utf8())?;
+
+    Ok((s, std::iter::repeat_n(pad, missing).collect()))
+}
+
+/// `(string/pad-left width s)`, or `(string/pad-left width pad s)` to pad
+/// with a character other than a space.
+pub const PAD_LEFT: Expression = Expression::Builtin {
+    name: "string/pad-left",
+    arity: Arity::between(2, 3),
+    function: |env, list| {
+        let (s, padding) = padding(env, list)?;
+
+        Ok(Expression::String(padding + &s))
+    },
+};
+
+/// `(string/pad-right width s)`, or `(string/pad-right width pad s)`.
+pub const PAD_RIGHT: Expression = Expression::Builtin {
+    name: "string/pad-right",
+    arity: Arity::between(2, 3),
+    function: |env, list| {
+        let (s, padding) = padding(env, list)?;
+
+        Ok(Expression::String(s + &padding))
+    },
+};
+
+/// `(string/repeat n s)`
+pub const REPEAT: Expression = Expression::Builtin {
+    name: "string/repeat",
+    arity: Arity::exactly(2),
+    function: |env, list| {
+        let n = eval_expression(env, &list[0])?.as_i64()?;
+        let s = string_arg(env, &list[1])?;
+
+        match usize::try_from(n) {
+            Ok(n) => {
+                repeated_size(n, s.len())?;
+
+                Ok(Expression::String(s.repeat(n)))
+            }
+            Err(_) => Err(error(
+                ErrorKind::Type,
+                format!("Negative repeat count: {n}"),
+            )),
+        }
+    },
+};
+
+/// `(string/join separator list)`
+pub const JOIN: Expression = Expression::Builtin {
+    name: "string/join",
+    arity: Arity::exactly(2),
+    function: |env, list| {
+        let separator = string_arg(env, &list[0])?;
+        let items = eval_expression(env, &list[1])?.as_list()?;
+
+        Ok(Expression::String(
+            items
+                .iter()
+                .map(text)
+                .collect::<Vec<String>>()
+                .join(&separator),
+        ))
+    },
+};
+
+/// `(string/lines s)`, splitting on `\n` or `\r\n`.
+pub const LINES: Expression = Expression::Builtin {
+    name: "string/lines",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let s = string_arg(env, &list[0])?;
+
+        Ok(Expression::List(
+            s.lines()
+                .map(|line| Expression::String(line.to_string()))
+                .collect(),
+        ))
+    },
+};
+
+/// `(string/chars s)`, a list of the characters of `s`.
+pub const CHARS: Expression = Expression::Builtin {
+    name: "string/chars",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let s = string_arg(env, &list[0])?;
+
+        Ok(Expression::List(s.chars().map(Expression::Char).collect()))
+    },
+};
+
+/// `(string/parse-int s)`, or `(string/parse-int radix s)`. Surrounding
+/// whitespace is ignored.
+pub const PARSE_INT: Expression = Expression::Builtin {
+    name: "string/parse-int",
+    arity: Arity::between(1, 2),
+    function: |env, list| {
+        let radix = match list {
+            [radix, _] => eval_expression(env, radix)?.as_i64()?,
+            _ => 10,
+        };
+        let s = string_arg(env, &list[list.len() - 1])?;
+
+        let radix = match u32::try_from(radix) {
+            Ok(radix @ 2..=36) => radix,
+            _ => {
+                return Err(error(
+                    ErrorKind::Arithmetic,
+                    format!("Radix must be between 2 and 36, got {radix}"),
+                ))
+            }
+        };
+
+        BigInt::parse_bytes(s.trim().as_bytes(), radix)
+            .map(Expression::from)
+            .ok_or_else(|| error(ErrorKind::Parse, format!("Not an integer: {s:?}")))
+    },
+};
+
+/// `(string/parse-float s)`, accepting whatever a float can be written as
+/// in Rust, such as `1.5`, `1e3` and `inf`.
+pub const PARSE_FLOAT: Expression = Expression::Builtin {
+    name: "string/parse-float",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let s = string_arg(env, &list[0])?;
+
+        s.trim()
+            .parse::<f64>()
+            .map(Expression::Float)
+            .map_err(|_| error(ErrorKind::Parse, format!("Not a float: {s:?}")))
+    },
+};
+
+/// `(string/format template value ...)`, filling each `{}` of `template`
+/// with the next value. `{{` and `}}` stand for literal braces.
+pub const FORMAT: Expression = Expression::Builtin {
+    name: "string/format",
+    arity: Arity::at_least(1),
+    function: |env, list| {
+        let template = string_arg(env, &list[0])?;
+        let mut values = list[1..].iter();
+        let mut formatted = String::with_capacity(template.len());
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    chars.next();
+                    formatted.push(c);
+                }
+                ('{', Some('}')) => {
+                    chars.next();
+
+                    let Some(value) = values.next() else {
+                        return Err(error(
+                            ErrorKind::Arity,
+                            format!("Not enough values for {template:?}"),
+                        ));
+                    };
+
+                    formatted.push_str(&text(&eval_expression(env, value)?));
+                }
+                _ => formatted.push(c),
+            }
+        }
+
+        if values.next().is_some() {
+            return Err(error(
+                ErrorKind::Arity,
+                format!("Too many values for {template:?}"),
+            ));
+        }
+
+        Ok(Expression::String(formatted))
+    },
+};
@@ -0,0 +1,199 @@
+use color_eyre::Result;
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    env::Env,
+    error::{error, ErrorKind},
+    eval::{eval_expression, eval_list, quoted},
+    expression::{Arity, Expression, Table},
+};
+
+/// Inserts the evaluated `key value ...` pairs of `list` into `table`.
+fn insert_pairs(env: &mut Rc<RefCell<Env>>, table: &mut Table, list: &[Expression]) -> Result<()> {
+    if !list.len().is_multiple_of(2) {
+        return Err(error(
+            ErrorKind::Arity,
+            format!("Missing a value for key {}", list[list.len() - 1]),
+        ));
+    }
+
+    for pair in list.chunks(2) {
+        let key = eval_expression(env, &pair[0])?.as_key()?;
+        let value = eval_expression(env, &pair[1])?;
+
+        table.insert(key, value);
+    }
+
+    Ok(())
+}
+
+/// `(table key value ...)`. Unlike in a table literal, the keys and values
+/// are evaluated.
+pub const TABLE: Expression = Expression::Builtin {
+    name: "table",
+    arity: Arity::at_least(0),
+    function: |env, list| {
+        let mut table = Table::new();
+
+        insert_pairs(env, &mut table, list)?;
+
+        Ok(Expression::Table(table))
+    },
+};
+
+/// `(get table key)`, `nil` or `default` when the key is missing.
+pub const GET: Expression = Expression::Builtin {
+    name: "get",
+    arity: Arity::between(2, 3),
+    function: |env, list| {
+        let table = eval_expression(env, &list[0])?.as_table()?;
+        let key = eval_expression(env, &list[1])?.as_key()?;
+
+        match table.get(&key) {
+            Some(value) => Ok(value.clone()),
+            None => match list.get(2) {
+                Some(default) => eval_expression(env, default),
+                None => Ok(Expression::Nil),
+            },
+        }
+    },
+};
+
+/// `(get-in table '(key ...))`, following the keys through nested tables.
+pub const GET_IN: Expression = Expression::Builtin {
+    name: "get-in",
+    arity: Arity::between(2, 3),
+    function: |env, list| {
+        let mut current = eval_expression(env, &list[0])?;
+
+        for k in eval_expression(env, &list[1])?.as_list()? {
+            let found = match &current {
+                Expression::Table(table) => table.get(&k.as_key()?).cloned(),
+                _ => None,
+            };
+
+            match found {
+                Some(value) => current = value,
+                None => {
+                    return match list.get(2) {
+                        Some(default) => eval_expression(env, default),
+                        None => Ok(Expression::Nil),
+                    }
+                }
+            }
+        }
+
+        Ok(current)
+    },
+};
+
+/// `(assoc table key value ...)`, a copy of `table` with the pairs set.
+pub const ASSOC: Expression = Expression::Builtin {
+    name: "assoc",
+    arity: Arity::at_least(3),
+    function: |env, list| {
+        let mut table = eval_expression(env, &list[0])?.as_table()?;
+
+        insert_pairs(env, &mut table, &list[1..])?;
+
+        Ok(Expression::Table(table))
+    },
+};
+
+/// `(dissoc table key ...)`, a copy of `table` without the keys.
+pub const DISSOC: Expression = Expression::Builtin {
+    name: "dissoc",
+    arity: Arity::at_least(2),
+    function: |env, list| {
+        let mut table = eval_expression(env, &list[0])?.as_table()?;
+
+        for k in &list[1..] {
+            table.shift_remove(&eval_expression(env, k)?.as_key()?);
+        }
+
+        Ok(Expression::Table(table))
+    },
+};
+
+/// `(update table key f)`, a copy of `table` with the value at `key`
+/// replaced by `f` of it, or of `nil` if it was missing.
+pub const UPDATE: Expression = Expression::Builtin {
+    name: "update",
+    arity: Arity::exactly(3),
+    function: |env, list| {
+        let mut table = eval_expression(env, &list[0])?.as_table()?;
+        let key = eval_expression(env, &list[1])?.as_key()?;
+        let f = eval_expression(env, &list[2])?;
+
+        let old = table.get(&key).cloned().unwrap_or(Expression::Nil);
+        let new = eval_list(env, &[f, quoted(old)])?;
+
+        table.insert(key, new);
+
+        Ok(Expression::Table(table))
+    },
+};
+
+pub const KEYS: Expression = Expression::Builtin {
+    name: "keys",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let table = eval_expression(env, &list[0])?.as_table()?;
+
+        Ok(Expression::List(
+            table.into_keys().map(Expression::from).collect(),
+        ))
+    },
+};
+
+pub const VALUES: Expression = Expression::Builtin {
+    name: "values",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let table = eval_expression(env, &list[0])?.as_table()?;
+
+        Ok(Expression::List(table.into_values().collect()))
+    },
+};
+
+/// `(entries table)`, a list of `(key value)` pairs.
+pub const ENTRIES: Expression = Expression::Builtin {
+    name: "entries",
+    arity: Arity::exactly(1),
+    function: |env, list| {
+        let table = eval_expression(env, &list[0])?.as_table()?;
+
+        Ok(Expression::List(
+            table
+                .into_iter()
+                .map(|(key, value)| Expression::List(vec![key.into(), value].into()))
+                .collect(),
+        ))
+    },
+};
+
+/// `(merge table ...)`, where later tables win on shared keys.
+pub const MERGE: Expression = Expression::Builtin {
+    name: "merge",
+    arity: Arity::at_least(1),
+    function: |env, list| {
+        let mut merged = Table::new();
+
+        for table in list {
+            merged.extend(eval_expression(env, table)?.as_table()?);
+        }
+
+        Ok(Expression::Table(merged))
+    },
+};
+
+pub const HAS_KEY: Expression = Expression::Builtin {
+    name: "has-key",
+    arity: Arity::exactly(2),
+    function: |env, list| {
+        let table = eval_expression(env, &list[0])?.as_table()?;
+        let key = eval_expression(env, &list[1])?.as_key()?;
+
+        Ok(table.contains_key(&key).into())
+    },
+};
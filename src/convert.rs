@@ -0,0 +1,322 @@
+pub use arcanya_derive::{FromExpression, IntoExpression};
+pub use color_eyre::Result;
+
+use color_eyre::eyre::WrapErr;
+use hashbrown::HashMap;
+use num_bigint::BigInt;
+use std::hash::BuildHasher;
+
+use crate::{
+    error::{error, ErrorKind},
+    expression::{Expression, Key, Table},
+};
+
+/// Rust values that have an Arcanya representation.
+///
+/// Derivable for structs, which become tables keyed by field name (lists
+/// for tuple structs), and enums, which become tables whose `variant` key
+/// names the variant, next to its fields.
+pub trait IntoExpression {
+    fn into_expression(self) -> Expression;
+}
+
+/// Rust values that can be read back from an `Expression`. Derivable like
+/// `IntoExpression`, reading the same shape.
+pub trait FromExpression: Sized {
+    fn from_expression(expression: &Expression) -> Result<Self>;
+}
+
+impl IntoExpression for Expression {
+    fn into_expression(self) -> Expression {
+        self
+    }
+}
+
+impl FromExpression for Expression {
+    fn from_expression(expression: &Expression) -> Result<Self> {
+        Ok(expression.clone())
+    }
+}
+
+/// Integers that don't fit in an `i64` become bignums.
+macro_rules! integers {
+    ($($t:ty),*) => {$(
+        impl IntoExpression for $t {
+            fn into_expression(self) -> Expression {
+                match i64::try_from(self) {
+                    Ok(i) => Expression::Integer(i),
+                    Err(_) => BigInt::from(self).into(),
+                }
+            }
+        }
+
+        impl FromExpression for $t {
+            fn from_expression(expression: &Expression) -> Result<Self> {
+                let n = BigInt::from_expression(expression)?;
+
+                <$t>::try_from(&n).map_err(|_| {
+                    error(
+                        ErrorKind::Type,
+                        format!("{n} doesn't fit in {}", stringify!($t)),
+                    )
+                })
+            }
+        }
+    )*};
+}
+
+integers!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl IntoExpression for BigInt {
+    fn into_expression(self) -> Expression {
+        self.into()
+    }
+}
+
+impl FromExpression for BigInt {
+    fn from_expression(expression: &Expression) -> Result<Self> {
+        match expression {
+            Expression::Integer(i) => Ok(BigInt::from(*i)),
+            Expression::BigInt(n) => Ok(n.clone()),
+            _ => Err(error(
+                ErrorKind::Type,
+                format!("Not an integer: {expression}"),
+            )),
+        }
+    }
+}
+
+macro_rules! floats {
+    ($($t:ty),*) => {$(
+        impl IntoExpression for $t {
+            fn into_expression(self) -> Expression {
+                Expression::Float(self as f64)
+            }
+        }
+
+        impl FromExpression for $t {
+            fn from_expression(expression: &Expression) -> Result<Self> {
+                match expression {
+                    Expression::Integer(i) => Ok(*i as $t),
+                    _ => Ok(expression.as_f64()? as $t),
+                }
+            }
+        }
+    )*};
+}
+
+floats!(f32, f64);
+
+impl IntoExpression for bool {
+    fn into_expression(self) -> Expression {
+        self.into()
+    }
+}
+
+/// Only booleans convert, with `nil` standing for `false`.
+impl FromExpression for bool {
+    fn from_expression(expression: &Expression) -> Result<Self> {
+        match expression {
+            Expression::Bool(b) => Ok(*b),
+            Expression::Nil => Ok(false),
+            _ => Err(error(
+                ErrorKind::Type,
+                format!("Not a boolean: {expression}"),
+            )),
+        }
+    }
+}
+
+impl IntoExpression for char {
+    fn into_expression(self) -> Expression {
+        Expression::Char(self)
+    }
+}
+
+impl FromExpression for char {
+    fn from_expression(expression: &Expression) -> Result<Self> {
+        expression.as_char()
+    }
+}
+
+impl IntoExpression for String {
+    fn into_expression(self) -> Expression {
+        Expression::String(self)
+    }
+}
+
+impl IntoExpression for &str {
+    fn into_expression(self) -> Expression {
+        Expression::String(self.to_string())
+    }
+}
+
+impl FromExpression for String {
+    fn from_expression(expression: &Expression) -> Result<Self> {
+        expression.as_string()
+    }
+}
+
+impl IntoExpression for () {
+    fn into_expression(self) -> Expression {
+        Expression::Nil
+    }
+}
+
+impl FromExpression for () {
+    fn from_expression(expression: &Expression) -> Result<Self> {
+        match expression {
+            Expression::Nil => Ok(()),
+            _ => Err(error(ErrorKind::Type, format!("Not nil: {expression}"))),
+        }
+    }
+}
+
+/// `None` is `nil`, `Some` is the value itself.
+impl<T: IntoExpression> IntoExpression for Option<T> {
+    fn into_expression(self) -> Expression {
+        self.map_or(Expression::Nil, T::into_expression)
+    }
+}
+
+impl<T: FromExpression> FromExpression for Option<T> {
+    fn from_expression(expression: &Expression) -> Result<Self> {
+        match expression {
+            Expression::Nil => Ok(None),
+            _ => T::from_expression(expression).map(Some),
+        }
+    }
+}
+
+impl<T: IntoExpression> IntoExpression for Vec<T> {
+    fn into_expression(self) -> Expression {
+        Expression::List(self.into_iter().map(T::into_expression).collect())
+    }
+}
+
+impl<T: FromExpression> FromExpression for Vec<T> {
+    fn from_expression(expression: &Expression) -> Result<Self> {
+        expression
+            .as_list()?
+            .iter()
+            .map(T::from_expression)
+            .collect()
+    }
+}
+
+impl<T: IntoExpression, S> IntoExpression for HashMap<String, T, S> {
+    fn into_expression(self) -> Expression {
+        Expression::Table(
+            self.into_iter()
+                .map(|(key, value)| (Key::String(key), value.into_expression()))
+                .collect(),
+        )
+    }
+}
+
+impl<T: FromExpression, S: BuildHasher + Default> FromExpression for HashMap<String, T, S> {
+    fn from_expression(expression: &Expression) -> Result<Self> {
+        expression
+            .as_table()?
+            .iter()
+            .map(|(key, value)| Ok((string_key(key)?, T::from_expression(value)?)))
+            .collect()
+    }
+}
+
+impl<T: IntoExpression, S> IntoExpression for std::collections::HashMap<String, T, S> {
+    fn into_expression(self) -> Expression {
+        Expression::Table(
+            self.into_iter()
+                .map(|(key, value)| (Key::String(key), value.into_expression()))
+                .collect(),
+        )
+    }
+}
+
+impl<T: FromExpression, S: BuildHasher + Default> FromExpression
+    for std::collections::HashMap<String, T, S>
+{
+    fn from_expression(expression: &Expression) -> Result<Self> {
+        expression
+            .as_table()?
+            .iter()
+            .map(|(key, value)| Ok((string_key(key)?, T::from_expression(value)?)))
+            .collect()
+    }
+}
+
+fn string_key(key: &Key) -> Result<String> {
+    match key {
+        Key::String(s) => Ok(s.clone()),
+        _ => Err(error(ErrorKind::Type, format!("Not a string key: {key}"))),
+    }
+}
+
+/// Tuples are lists of exactly their length.
+macro_rules! tuples {
+    ($(($($t:ident $i:tt),+))*) => {$(
+        impl<$($t: IntoExpression),+> IntoExpression for ($($t,)+) {
+            fn into_expression(self) -> Expression {
+                Expression::List(vec![$(self.$i.into_expression()),+].into())
+            }
+        }
+
+        impl<$($t: FromExpression),+> FromExpression for ($($t,)+) {
+            fn from_expression(expression: &Expression) -> Result<Self> {
+                let list = expression.as_list()?;
+                let length = [$($i),+].len();
+
+                if list.len() != length {
+                    return Err(error(
+                        ErrorKind::Type,
+                        format!("Expected a list of {length}, got {expression}"),
+                    ));
+                }
+
+                Ok(($($t::from_expression(&list[$i])?,)+))
+            }
+        }
+    )*};
+}
+
+tuples! {
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+    (A 0, B 1, C 2, D 3, E 4)
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+}
+
+/// Reads field `name` of a derived type from `table`, where a missing key
+/// reads as `nil`.
+#[doc(hidden)]
+pub fn field<T: FromExpression>(table: &Table, name: &str) -> Result<T> {
+    T::from_expression(table.get(&Key::from(name)).unwrap_or(&Expression::Nil))
+        .wrap_err_with(|| format!("In field `{name}`"))
+}
+
+/// Reads element `index` of a derived tuple struct or variant.
+#[doc(hidden)]
+pub fn element<T: FromExpression>(list: &[Expression], index: usize) -> Result<T> {
+    T::from_expression(list.get(index).unwrap_or(&Expression::Nil))
+        .wrap_err_with(|| format!("In element {index}"))
+}
+
+/// The table of a derived enum along with its variant name.
+#[doc(hidden)]
+pub fn variant(expression: &Expression) -> Result<(String, Table)> {
+    let table = expression.as_table()?;
+    let name = field::<String>(&table, "variant")?;
+
+    Ok((name, table))
+}
+
+#[doc(hidden)]
+pub fn unknown_variant(name: &str, expected: &str) -> color_eyre::Report {
+    error(
+        ErrorKind::Type,
+        format!("Unknown variant `{name}` of {expected}"),
+    )
+}
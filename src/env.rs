@@ -1,7 +1,11 @@
+use color_eyre::Result;
 use hashbrown::HashMap;
 use std::{cell::RefCell, rc::Rc};
 
-use crate::expression::Expression;
+use crate::{
+    expression::{Arity, Expression, Key, Table},
+    interpreter::Interpreter,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Env {
@@ -14,8 +18,8 @@ impl Env {
         Env {
             parent,
             local: HashMap::from([
-                ("__EXPORTED".to_string(), Expression::Table(HashMap::new())),
-                ("__IMPORTED".to_string(), Expression::Table(HashMap::new())),
+                ("__EXPORTED".to_string(), Expression::Table(Table::new())),
+                ("__IMPORTED".to_string(), Expression::Table(Table::new())),
             ]),
         }
     }
@@ -29,13 +33,11 @@ impl Env {
                     .as_ref()
                     .and_then(|parent| parent.borrow().get(symbol))
             })
-            .or_else(|| {
-                self.get("__IMPORTED")
-                    .unwrap()
-                    .as_table()
-                    .unwrap()
-                    .get(symbol)
-                    .cloned()
+            .or_else(|| match self.local.get("__IMPORTED") {
+                Some(Expression::Table(imported)) => {
+                    imported.get(&Key::Symbol(symbol.to_string())).cloned()
+                }
+                _ => None,
             })
     }
 
@@ -79,7 +81,36 @@ impl Env {
         }
     }
 
+    /// Binds a Rust closure under `name` in this scope.
+    pub fn register(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Interpreter, &[Expression]) -> Result<Expression> + 'static,
+    ) {
+        self.set_local(name.to_string(), Expression::native(name, arity, function));
+    }
+
     pub fn extend(&mut self, other: Env) {
         self.local.extend(other.local);
     }
 }
+
+/// The environment a function closes over.
+///
+/// Compared by identity and printed opaquely, since a global function
+/// captures the very environment it is stored in.
+#[derive(Clone)]
+pub struct CapturedEnv(pub Rc<RefCell<Env>>);
+
+impl PartialEq for CapturedEnv {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::fmt::Debug for CapturedEnv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CapturedEnv({:p})", Rc::as_ptr(&self.0))
+    }
+}
@@ -0,0 +1,140 @@
+use color_eyre::Report;
+use hashbrown::HashMap;
+use std::{
+    cell::RefCell,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    expression::{Expression, Key, Table},
+    source::Located,
+};
+
+/// Broad category of an error, as seen by `catch`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    Type,
+    Arity,
+    Arithmetic,
+    Parse,
+    Io,
+    Thrown,
+    Other,
+}
+
+impl ErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Type => "type",
+            ErrorKind::Arity => "arity",
+            ErrorKind::Arithmetic => "arithmetic",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Io => "io",
+            ErrorKind::Thrown => "thrown",
+            ErrorKind::Other => "error",
+        }
+    }
+}
+
+/// An interpreter error with a known kind.
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub fn error(kind: ErrorKind, message: impl Into<String>) -> Report {
+    Report::new(Error {
+        kind,
+        message: message.into(),
+    })
+}
+
+thread_local! {
+    static THROWN: RefCell<HashMap<usize, Expression>> = RefCell::new(HashMap::new());
+}
+
+static THROWN_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A value raised with `throw`. Reports have to be `Send`, so the value
+/// itself waits in a thread local table until it is caught or dropped.
+#[derive(Debug)]
+pub struct Thrown {
+    id: usize,
+    message: String,
+}
+
+impl Thrown {
+    pub fn value(&self) -> Expression {
+        THROWN
+            .with(|thrown| thrown.borrow().get(&self.id).cloned())
+            .unwrap_or(Expression::Nil)
+    }
+}
+
+impl std::fmt::Display for Thrown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Thrown {}
+
+impl Drop for Thrown {
+    fn drop(&mut self) {
+        let _ = THROWN.try_with(|thrown| thrown.borrow_mut().remove(&self.id));
+    }
+}
+
+pub fn throw(value: Expression) -> Report {
+    let id = THROWN_COUNT.fetch_add(1, Ordering::SeqCst);
+
+    let message = match &value {
+        Expression::String(s) => s.clone(),
+        _ => value.to_string(),
+    };
+
+    THROWN.with(|thrown| thrown.borrow_mut().insert(id, value));
+
+    Report::new(Thrown { id, message })
+}
+
+/// The table a `catch` clause binds: `message`, `kind`, `trace` and, for
+/// thrown values, the `value` itself.
+pub fn caught(report: &Report) -> Expression {
+    let (error, trace) = match report.downcast_ref::<Located>() {
+        Some(located) => (&located.error, located.trace.clone()),
+        None => (report, vec![]),
+    };
+
+    let (kind, value) = if let Some(thrown) = error.downcast_ref::<Thrown>() {
+        (ErrorKind::Thrown, thrown.value())
+    } else if let Some(error) = error.downcast_ref::<Error>() {
+        (error.kind, Expression::Nil)
+    } else if error.downcast_ref::<std::io::Error>().is_some() {
+        (ErrorKind::Io, Expression::Nil)
+    } else {
+        (ErrorKind::Other, Expression::Nil)
+    };
+
+    Expression::Table(Table::from([
+        (Key::from("message"), Expression::String(error.to_string())),
+        (
+            Key::from("kind"),
+            Expression::String(kind.name().to_string()),
+        ),
+        (
+            Key::from("trace"),
+            Expression::List(trace.into_iter().map(Expression::String).collect()),
+        ),
+        (Key::from("value"), value),
+    ]))
+}
@@ -1,40 +1,142 @@
-use color_eyre::{Result, Section};
+use color_eyre::{eyre::eyre, Report, Result, Section};
 use hashbrown::HashMap;
 use std::{
+    borrow::Cow,
     cell::RefCell,
     rc::Rc,
     sync::atomic::{AtomicUsize, Ordering},
 };
 
-use crate::{env::Env, expression::Expression};
+use crate::{
+    env::{CapturedEnv, Env},
+    error::{error, ErrorKind},
+    expression::{Arity, Expression, List},
+    interpreter::Interpreter,
+    source::{self, Located, Location},
+};
 
 pub const DEBUG_MODE: bool = false;
 pub static EVALUATION_COUNT: AtomicUsize = AtomicUsize::new(0);
 #[allow(dead_code)]
 pub static LAST_EVALUATION_COUNT: AtomicUsize = AtomicUsize::new(0);
 
+/// A call in progress, kept so errors can report how they were reached.
+struct Frame {
+    form: List,
+    /// What the callee was called by or, for functions passed around as
+    /// values, the name it was defined under.
+    name: String,
+    /// Evaluated arguments, for calls that evaluate them up front.
+    arguments: Option<Vec<Expression>>,
+}
+
+thread_local! {
+    static CALL_STACK: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+fn stack_depth() -> usize {
+    CALL_STACK.with(|stack| stack.borrow().len())
+}
+
+fn truncate_stack(depth: usize) {
+    CALL_STACK.with(|stack| stack.borrow_mut().truncate(depth));
+}
+
+/// Result of a call whose last step may be left to the caller.
+///
+/// Returning `Eval` instead of evaluating in place lets `eval_expression`
+/// reuse its frame for calls in tail position.
+pub enum Tail {
+    Value(Expression),
+    Eval(Rc<RefCell<Env>>, Expression),
+}
+
+impl Tail {
+    pub fn finish(self) -> Result<Expression> {
+        match self {
+            Tail::Value(value) => Ok(value),
+            Tail::Eval(mut env, expr) => eval_expression(&mut env, &expr),
+        }
+    }
+}
+
 pub fn eval_expression(env: &mut Rc<RefCell<Env>>, expr: &Expression) -> Result<Expression> {
     match expr {
-        Expression::Integer(_)
-        | Expression::String(_)
-        | Expression::Builtin {
-            name: _,
-            function: _,
-        }
-        | Expression::Float(_)
-        | Expression::Function {
-            arguments: _,
-            body: _,
+        Expression::Symbol(s) => return Ok(env.borrow().get(s).unwrap_or(Expression::Nil)),
+        Expression::List(_) => {}
+        _ => return Ok(expr.clone()),
+    }
+
+    let depth = stack_depth();
+    let result = eval_tail_calls(env, expr);
+
+    truncate_stack(depth);
+
+    result
+}
+
+/// The trampoline. A call in tail position replaces the frame of the call
+/// that handed it over, so only the newest one stays on the call stack.
+fn eval_tail_calls(env: &mut Rc<RefCell<Env>>, expr: &Expression) -> Result<Expression> {
+    let depth = stack_depth();
+    let mut env = env.clone();
+    let mut expr = Cow::Borrowed(expr);
+
+    loop {
+        let tail = match expr.as_ref() {
+            Expression::Bool(_)
+            | Expression::Integer(_)
+            | Expression::BigInt(_)
+            | Expression::Rational(_)
+            | Expression::Char(_)
+            | Expression::String(_)
+            | Expression::Builtin { .. }
+            | Expression::SpecialForm { .. }
+            | Expression::Native { .. }
+            | Expression::Float(_)
+            | Expression::Function { .. }
+            | Expression::Macro { .. }
+            | Expression::Table(_)
+            | Expression::Keyword(_)
+            | Expression::Nil => return Ok(expr.into_owned()),
+            Expression::Symbol(s) => return Ok(env.borrow().get(s).unwrap_or(Expression::Nil)),
+            Expression::List(l) => eval_call(&mut env, l)?,
+        };
+
+        match tail {
+            Tail::Value(value) => return Ok(value),
+            Tail::Eval(next_env, next) => {
+                CALL_STACK.with(|stack| {
+                    let mut stack = stack.borrow_mut();
+                    let newest = stack.len().saturating_sub(1).max(depth);
+
+                    stack.drain(depth..newest);
+                });
+
+                env = next_env;
+                expr = Cow::Owned(next);
+            }
         }
-        | Expression::Table(_)
-        | Expression::Nil => Ok(expr.clone()),
-        Expression::Symbol(s) => Ok(env.borrow().get(s).unwrap_or(Expression::Nil)),
-        Expression::List(l) => eval_list(env, l),
     }
 }
 
 pub fn eval_list(env: &mut Rc<RefCell<Env>>, list: &[Expression]) -> Result<Expression> {
-    let mut caller = eval_expression(env, &list[0])?;
+    let depth = stack_depth();
+    let result = eval_call(env, &List::new(list.to_vec())).and_then(Tail::finish);
+
+    truncate_stack(depth);
+
+    result
+}
+
+/// Pushes a frame for the call and leaves it there when returning
+/// `Tail::Eval`, for the trampoline to drop once the tail call is done.
+fn eval_call(env: &mut Rc<RefCell<Env>>, list: &List) -> Result<Tail> {
+    let Some(head) = list.first() else {
+        return Err(error(ErrorKind::Arity, "Can't call an empty list"));
+    };
+
+    let mut caller = eval_expression(env, head)?;
 
     while let Expression::List(_) = caller {
         caller = eval_expression(env, &caller)?;
@@ -43,60 +145,454 @@ pub fn eval_list(env: &mut Rc<RefCell<Env>>, list: &[Expression]) -> Result<Expr
     EVALUATION_COUNT.fetch_add(1, Ordering::SeqCst);
 
     if DEBUG_MODE {
-        println!("{}", Expression::List(list.to_vec()).as_debug_string());
+        println!(
+            "{}",
+            Expression::List(list.to_vec().into()).as_debug_string()
+        );
+    }
+
+    let depth = stack_depth();
+
+    CALL_STACK.with(|stack| {
+        stack.borrow_mut().push(Frame {
+            form: list.clone(),
+            name: callee_name(head, &caller),
+            arguments: None,
+        })
+    });
+
+    // Special forms and macros continue the enclosing call, so their frame
+    // goes away as soon as they hand over a tail.
+    let transparent = matches!(
+        caller,
+        Expression::SpecialForm { .. } | Expression::Macro { .. }
+    );
+
+    let result = apply(env, list, caller).map_err(traced);
+
+    match result {
+        Ok(Tail::Eval(..)) if !transparent => {}
+        _ => truncate_stack(depth),
     }
 
+    result
+}
+
+fn apply(env: &mut Rc<RefCell<Env>>, list: &[Expression], caller: Expression) -> Result<Tail> {
     match caller {
         Expression::Function {
             ref arguments,
             ref body,
+            env: ref captured,
+            ..
         } => {
-            if arguments.len() != list.len() - 1 || list.contains(&Expression::Symbol("_".into())) {
-                if list.len() == 1 {
-                    Ok(caller)
-                } else if let Expression::List(_) = *(body.clone()) {
-                    let current_arguments = &list[1..];
-
-                    let new_arguments = &arguments[current_arguments.len()..];
-                    let mut new_body = vec![caller.clone()];
-                    new_body.extend(current_arguments.iter().cloned());
-
-                    Ok(Expression::Function {
-                        arguments: new_arguments.to_vec(),
-                        body: Box::new(Expression::List(new_body)),
-                    })
+            let supplied = list.len() - 1;
+
+            if supplied > arguments.len() {
+                let name = CALL_STACK.with(|stack| stack.borrow().last().map(|f| f.name.clone()));
+
+                return Err(error(
+                    ErrorKind::Arity,
+                    format!(
+                        "function `{}` expects {}, got {supplied}",
+                        name.unwrap_or_default(),
+                        Arity::exactly(arguments.len())
+                    ),
+                ));
+            }
+
+            if supplied < arguments.len() || has_placeholders(&list[1..]) {
+                if supplied == 0 {
+                    Ok(Tail::Value(caller))
                 } else {
-                    Ok(Expression::Nil)
+                    partial(env, &caller, &list[1..], arguments).map(Tail::Value)
                 }
             } else {
-                let mut e = Rc::new(RefCell::new(Env {
-                    parent: Some(env.clone()),
+                let e = Rc::new(RefCell::new(Env {
+                    parent: Some(captured.0.clone()),
                     local: HashMap::new(),
                 }));
 
-                let current_arguments = &list[1..];
+                let values = list[1..]
+                    .iter()
+                    .map(|argument| eval_expression(env, argument))
+                    .collect::<Result<Vec<Expression>>>()?;
+
+                for (argument, value) in arguments.iter().zip(&values) {
+                    e.as_ref()
+                        .borrow_mut()
+                        .set_local(argument.as_symbol_string()?, value.clone());
+                }
+
+                CALL_STACK.with(|stack| {
+                    if let Some(frame) = stack.borrow_mut().last_mut() {
+                        frame.arguments = Some(values);
+                    }
+                });
+
+                Ok(Tail::Eval(e, *body.clone()))
+            }
+        }
+        Expression::Macro {
+            ref arguments,
+            ref body,
+            env: ref captured,
+        } => {
+            let expansion = expand_macro(arguments, body, captured, &list[1..])?;
+
+            Ok(Tail::Eval(env.clone(), expansion))
+        }
+        // Builtins taking any number of arguments can't tell a call that is
+        // short of arguments from a complete one, so they only partially
+        // apply with placeholders.
+        Expression::Builtin { arity, .. } | Expression::Native { arity, .. }
+            if (arity.max.is_some() && list.len() - 1 < arity.min)
+                || has_placeholders(&list[1..]) =>
+        {
+            if list.len() == 1 {
+                return Ok(Tail::Value(caller));
+            }
+
+            let parameters = (0..arity.min.max(list.len() - 1))
+                .map(|i| Expression::Symbol(format!("_{i}")))
+                .collect::<Vec<Expression>>();
+
+            partial(env, &caller, &list[1..], &parameters).map(Tail::Value)
+        }
+        Expression::Builtin {
+            name,
+            arity,
+            function,
+        } => {
+            check_arity(name, arity, list.len() - 1)?;
+
+            function(env, &list[1..]).map(Tail::Value)
+        }
+        Expression::SpecialForm {
+            name,
+            arity,
+            function,
+        } => {
+            check_arity(name, arity, list.len() - 1)?;
+
+            function(env, &list[1..])
+        }
+        Expression::Native {
+            ref name,
+            arity,
+            ref function,
+        } => {
+            check_arity(name, arity, list.len() - 1)?;
+
+            let values = list[1..]
+                .iter()
+                .map(|argument| eval_expression(env, argument))
+                .collect::<Result<Vec<Expression>>>()?;
+
+            CALL_STACK.with(|stack| {
+                if let Some(frame) = stack.borrow_mut().last_mut() {
+                    frame.arguments = Some(values.clone());
+                }
+            });
+
+            (function.0)(&mut Interpreter::from_shared(env.clone()), &values).map(Tail::Value)
+        }
+        Expression::List(l) => eval_list(env, &l).map(Tail::Value),
+        _ => Ok(Tail::Value(caller)),
+    }
+}
+
+fn has_placeholders(arguments: &[Expression]) -> bool {
+    arguments
+        .iter()
+        .any(|argument| matches!(argument, Expression::Symbol(s) if s == "_"))
+}
+
+/// Applies `caller` to the `arguments` given so far, returning a function of
+/// the rest: the `_` placeholders and whatever is missing at the end, named
+/// after the matching `parameters`. The given arguments are evaluated now.
+fn partial(
+    env: &mut Rc<RefCell<Env>>,
+    caller: &Expression,
+    arguments: &[Expression],
+    parameters: &[Expression],
+) -> Result<Expression> {
+    let mut remaining = vec![];
+    let mut call = vec![caller.clone()];
+
+    for (argument, parameter) in arguments.iter().zip(parameters) {
+        if has_placeholders(std::slice::from_ref(argument)) {
+            remaining.push(parameter.clone());
+            call.push(parameter.clone());
+        } else {
+            call.push(quoted(eval_expression(env, argument)?));
+        }
+    }
+
+    remaining.extend(parameters.iter().skip(arguments.len()).cloned());
+    call.extend(parameters.iter().skip(arguments.len()).cloned());
+
+    Ok(Expression::Function {
+        arguments: remaining,
+        body: Box::new(Expression::List(call.into())),
+        env: CapturedEnv(env.clone()),
+        name: None,
+    })
+}
+
+/// An expression evaluating to `value`, quoting it when it would otherwise
+/// be looked up or called.
+pub fn quoted(value: Expression) -> Expression {
+    match value {
+        Expression::List(_) | Expression::Symbol(_) => {
+            Expression::List(vec![Expression::Symbol("quote".to_string()), value].into())
+        }
+        value => value,
+    }
+}
+
+fn check_arity(name: &str, arity: Arity, count: usize) -> Result<()> {
+    if arity.accepts(count) {
+        Ok(())
+    } else {
+        Err(error(
+            ErrorKind::Arity,
+            format!("builtin `{name}` expects {arity}, got {count}"),
+        ))
+    }
+}
+
+/// Evaluates a macro body with its parameters bound to the unevaluated
+/// `values`, producing the form that replaces the macro call.
+pub fn expand_macro(
+    arguments: &[Expression],
+    body: &Expression,
+    captured: &CapturedEnv,
+    values: &[Expression],
+) -> Result<Expression> {
+    let mut macro_env = Rc::new(RefCell::new(Env {
+        parent: Some(captured.0.clone()),
+        local: HashMap::new(),
+    }));
 
-                for i in 0..arguments.len() {
-                    e.as_ref().borrow_mut().set_local(
-                        arguments[i].as_symbol_string()?,
-                        eval_expression(env, &current_arguments[i])?,
-                    );
+    bind_macro_arguments(&mut macro_env.borrow_mut(), arguments, values)?;
+
+    eval_expression(&mut macro_env, body)
+}
+
+/// Expands the macro calls in `form` before it is evaluated, so a macro in
+/// a loop or function body is expanded once rather than on every call, and a
+/// failing expansion stops the form before any of it has run.
+///
+/// Quoted data is left alone, except for the bodies handed to `function` and
+/// `for`. Macros defined while the form runs are expanded when called.
+pub fn expand(env: &Rc<RefCell<Env>>, form: &Expression) -> Result<Expression> {
+    Ok(expanded(env, form)?.unwrap_or_else(|| form.clone()))
+}
+
+/// The expansion of `form`, or `None` when there is nothing to expand, so
+/// untouched lists keep their location.
+fn expanded(env: &Rc<RefCell<Env>>, form: &Expression) -> Result<Option<Expression>> {
+    let Expression::List(list) = form else {
+        return Ok(None);
+    };
+
+    let head = match list.first() {
+        Some(Expression::Symbol(head)) => Some(head.as_str()),
+        _ => None,
+    };
+
+    // Where `function` and `for` take their quoted body.
+    let code = match head {
+        Some("quote" | "quasiquote") => return Ok(None),
+        Some("function") => Some(2),
+        Some("for") => Some(3),
+        _ => None,
+    };
+
+    let callee = head.and_then(|head| env.borrow().get(head));
+
+    if let Some(Expression::Macro {
+        arguments,
+        body,
+        env: captured,
+    }) = callee
+    {
+        let expansion =
+            expand_macro(&arguments, &body, &captured, &list[1..]).map_err(|error| {
+                match list.location() {
+                    Some(location) if error.downcast_ref::<Located>().is_none() => {
+                        source::located(error, location.excerpt())
+                    }
+                    _ => error,
                 }
+            })?;
+
+        // Errors in the expansion point at the macro call it came from.
+        let expansion = match (expansion, list.location()) {
+            (Expression::List(l), Some(location)) if l.location().is_none() => {
+                Expression::List(List::located(l.into_vec(), location.clone()))
+            }
+            (expansion, _) => expansion,
+        };
+
+        return expand(env, &expansion).map(Some);
+    }
+
+    let mut items: Option<Vec<Expression>> = None;
+
+    for (i, item) in list.iter().enumerate() {
+        let item = match item {
+            Expression::List(quote) if Some(i) == code && is_quote(quote) => {
+                expanded(env, &quote[1])?.map(|inner| {
+                    let mut quote = quote.clone();
+                    quote[1] = inner;
+                    Expression::List(quote)
+                })
+            }
+            item => expanded(env, item)?,
+        };
+
+        if let Some(item) = item {
+            items.get_or_insert_with(|| list.to_vec())[i] = item;
+        }
+    }
+
+    Ok(items.map(|items| {
+        Expression::List(match list.location() {
+            Some(location) => List::located(items, location.clone()),
+            None => List::new(items),
+        })
+    }))
+}
+
+fn is_quote(list: &List) -> bool {
+    matches!(&list[..], [Expression::Symbol(quote), _] if quote == "quote")
+}
+
+/// Binds macro parameters, where `& rest` collects the remaining values and
+/// a nested parameter list destructures the corresponding value.
+fn bind_macro_arguments(
+    env: &mut Env,
+    parameters: &[Expression],
+    values: &[Expression],
+) -> Result<()> {
+    let mut values = values.iter();
+    let mut parameters = parameters.iter();
+
+    while let Some(parameter) = parameters.next() {
+        match parameter {
+            Expression::Symbol(s) if s == "&" => {
+                let rest = parameters
+                    .next()
+                    .ok_or_else(|| eyre!("Missing rest parameter after &"))?;
+
+                env.set_local(
+                    rest.as_symbol_string()?,
+                    Expression::List(values.cloned().collect()),
+                );
+
+                return Ok(());
+            }
+            Expression::List(nested) => {
+                let value = values
+                    .next()
+                    .ok_or_else(|| eyre!("Missing macro argument for {parameter}"))?;
+
+                bind_macro_arguments(env, nested, &value.as_list()?)?;
+            }
+            _ => {
+                let value = values
+                    .next()
+                    .ok_or_else(|| eyre!("Missing macro argument for {parameter}"))?;
 
-                eval_expression(&mut e, &body)
+                env.set_local(parameter.as_symbol_string()?, value.clone());
             }
         }
-        // TODO: Partial application on Builtins
-        Expression::Builtin { name: _, function } => function(env, &list[1..]).map_err(|e| {
-            e.note(format!(
-                "Evaluating: ({})",
-                list.iter()
-                    .map(|x| x.to_string())
-                    .collect::<Vec<String>>()
-                    .join(" ")
-            ))
-        }),
-        Expression::List(l) => eval_list(env, &l),
-        _ => Ok(caller),
     }
+
+    if values.next().is_some() {
+        return Err(eyre!("Too many macro arguments"));
+    }
+
+    Ok(())
+}
+
+/// The name a call is reported under: the symbol it was made through or,
+/// for a callee that was passed around as a value, its own name.
+fn callee_name(head: &Expression, callee: &Expression) -> String {
+    match (head, callee) {
+        (Expression::Symbol(name), _) => name.clone(),
+        (_, Expression::Builtin { name, .. } | Expression::SpecialForm { name, .. }) => {
+            name.to_string()
+        }
+        (_, Expression::Native { name, .. }) => name.clone(),
+        (_, Expression::Function { name, .. }) => {
+            name.clone().unwrap_or_else(|| "<anonymous>".to_string())
+        }
+        (head, _) => head.as_debug_string(),
+    }
+}
+
+impl Frame {
+    fn describe(&self) -> String {
+        let arguments = match &self.arguments {
+            Some(arguments) => arguments,
+            None => &self.form[1..],
+        };
+
+        let call = std::iter::once(self.name.clone())
+            .chain(arguments.iter().map(|argument| argument.as_debug_string()))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        match self.form.location() {
+            Some(location) => format!("({call}) at {}", location.position()),
+            None => format!("({call})"),
+        }
+    }
+}
+
+/// Snapshots the call stack into `error` the first time it leaves a call,
+/// along with the innermost location known for it.
+fn traced(error: Report) -> Report {
+    if error.downcast_ref::<Located>().is_some() {
+        return error;
+    }
+
+    CALL_STACK.with(|stack| {
+        let stack = stack.borrow();
+
+        let location = stack
+            .iter()
+            .rev()
+            .find_map(|frame| frame.form.location())
+            .map(Location::excerpt);
+
+        let trace = stack
+            .iter()
+            .rev()
+            .map(Frame::describe)
+            .collect::<Vec<String>>();
+
+        let mut report = Report::new(Located {
+            error,
+            location: location.clone(),
+            trace: trace.clone(),
+        });
+
+        if let Some(location) = location {
+            report = report.section(location);
+        }
+
+        report.section(format!(
+            "Traceback (most recent call first):\n{}",
+            trace
+                .iter()
+                .enumerate()
+                .map(|(i, frame)| format!("{i:>4}: {frame}"))
+                .collect::<Vec<String>>()
+                .join("\n")
+        ))
+    })
 }
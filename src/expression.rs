@@ -1,43 +1,414 @@
-use color_eyre::{eyre::eyre, Result};
+use color_eyre::Result;
 use colored::Colorize;
 // use core::hash::Hasher;
-use hashbrown::HashMap;
+use indexmap::IndexMap;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::ToPrimitive;
 // use std::hash::Hash;
-use lazy_static::lazy_static;
 use std::{cell::RefCell, rc::Rc};
 
-use crate::env::Env;
+use crate::{
+    env::{CapturedEnv, Env},
+    error::{error, ErrorKind},
+    eval::Tail,
+    interpreter::Interpreter,
+    source::Location,
+};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Expression {
+    Bool(bool),
     Integer(i64),
+    /// An integer too large for an `i64`. Arithmetic moves between the two
+    /// as needed, so an integer that fits is always an `Integer`.
+    BigInt(BigInt),
+    /// An exact fraction, as made by dividing integers that don't divide
+    /// evenly. Always in lowest terms, with a denominator other than 1.
+    Rational(BigRational),
     Float(f64),
+    /// A single Unicode scalar value, written `#\a`.
+    Char(char),
     String(String),
     Symbol(String),
-    List(Vec<Expression>),
-    Table(HashMap<String, Expression>),
+    /// A symbol that evaluates to itself, written `:name`.
+    Keyword(String),
+    List(List),
+    Table(Table),
     Function {
         arguments: Vec<Expression>,
         body: Box<Expression>,
+        env: CapturedEnv,
+        /// The name it was first defined under, for tracebacks. Ignored when
+        /// comparing.
+        name: Option<String>,
+    },
+    /// Like a function, but receives its arguments unevaluated and returns
+    /// the form to evaluate in their place.
+    Macro {
+        arguments: Vec<Expression>,
+        body: Box<Expression>,
+        env: CapturedEnv,
     },
     Builtin {
         name: &'static str,
+        arity: Arity,
         function: fn(&mut Rc<RefCell<Env>>, &[Expression]) -> Result<Expression>,
     },
+    /// A builtin working on its arguments as written, like `quote` or `if`.
+    /// It is never partially applied, and may hand its final expression back
+    /// to the evaluator, so that expression is evaluated in tail position.
+    SpecialForm {
+        name: &'static str,
+        arity: Arity,
+        function: fn(&mut Rc<RefCell<Env>>, &[Expression]) -> Result<Tail>,
+    },
+    /// A Rust closure registered by the host program. Unlike the other
+    /// builtins it receives its arguments already evaluated.
+    Native {
+        name: String,
+        arity: Arity,
+        function: NativeFunction,
+    },
     Nil,
 }
 
-lazy_static! {
-    pub static ref NIL: Expression = Expression::Nil;
-    pub static ref TRUE: Expression = Expression::Symbol(String::from("t"));
+/// Structural equality, except that builtins compare by name: the same
+/// function pointer isn't guaranteed a single address.
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Expression::Bool(a), Expression::Bool(b)) => a == b,
+            (Expression::Integer(a), Expression::Integer(b)) => a == b,
+            (Expression::BigInt(a), Expression::BigInt(b)) => a == b,
+            (Expression::Rational(a), Expression::Rational(b)) => a == b,
+            (Expression::Float(a), Expression::Float(b)) => a == b,
+            (Expression::Char(a), Expression::Char(b)) => a == b,
+            (Expression::String(a), Expression::String(b))
+            | (Expression::Symbol(a), Expression::Symbol(b))
+            | (Expression::Keyword(a), Expression::Keyword(b)) => a == b,
+            (Expression::List(a), Expression::List(b)) => a == b,
+            (Expression::Table(a), Expression::Table(b)) => a == b,
+            (
+                Expression::Function {
+                    arguments: a,
+                    body: b,
+                    env: e,
+                    ..
+                },
+                Expression::Function {
+                    arguments: x,
+                    body: y,
+                    env: z,
+                    ..
+                },
+            )
+            | (
+                Expression::Macro {
+                    arguments: a,
+                    body: b,
+                    env: e,
+                },
+                Expression::Macro {
+                    arguments: x,
+                    body: y,
+                    env: z,
+                },
+            ) => a == x && b == y && e == z,
+            (Expression::Builtin { name: a, .. }, Expression::Builtin { name: b, .. })
+            | (Expression::SpecialForm { name: a, .. }, Expression::SpecialForm { name: b, .. }) => {
+                a == b
+            }
+            (
+                Expression::Native {
+                    name: a,
+                    function: f,
+                    ..
+                },
+                Expression::Native {
+                    name: b,
+                    function: g,
+                    ..
+                },
+            ) => a == b && f == g,
+            (Expression::Nil, Expression::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+pub const NIL: Expression = Expression::Nil;
+pub const TRUE: Expression = Expression::Bool(true);
+
+/// The items of a list. Copies share them, so cloning a list, as happens to
+/// a function body on every call, is cheap. A list read from source also
+/// remembers where, for error messages.
+#[derive(Clone, Default)]
+pub struct List {
+    items: Rc<Vec<Expression>>,
+    location: Option<Location>,
+}
+
+impl List {
+    pub fn new(items: Vec<Expression>) -> List {
+        List {
+            items: Rc::new(items),
+            location: None,
+        }
+    }
+
+    pub fn located(items: Vec<Expression>, location: Location) -> List {
+        List {
+            items: Rc::new(items),
+            location: Some(location),
+        }
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        self.location.as_ref()
+    }
+
+    pub fn into_vec(self) -> Vec<Expression> {
+        Rc::unwrap_or_clone(self.items)
+    }
+}
+
+impl std::ops::Deref for List {
+    type Target = Vec<Expression>;
+
+    fn deref(&self) -> &Vec<Expression> {
+        &self.items
+    }
+}
+
+/// Copies the items first if another list still shares them.
+impl std::ops::DerefMut for List {
+    fn deref_mut(&mut self) -> &mut Vec<Expression> {
+        Rc::make_mut(&mut self.items)
+    }
+}
+
+/// Lists are equal when their items are, wherever they were read from.
+impl PartialEq for List {
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+    }
+}
+
+impl std::fmt::Debug for List {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.items.fmt(f)
+    }
+}
+
+impl From<Vec<Expression>> for List {
+    fn from(items: Vec<Expression>) -> List {
+        List::new(items)
+    }
+}
+
+impl FromIterator<Expression> for List {
+    fn from_iter<I: IntoIterator<Item = Expression>>(iter: I) -> List {
+        List::new(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for List {
+    type Item = Expression;
+    type IntoIter = std::vec::IntoIter<Expression>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_vec().into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a List {
+    type Item = &'a Expression;
+    type IntoIter = std::slice::Iter<'a, Expression>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+/// The contents of a table, which keeps its keys in the order they were
+/// first inserted, so tables print and iterate the same way every run.
+pub type Table = IndexMap<Key, Expression>;
+
+/// A table key. Only values whose equality is exact can be keys, so floats
+/// and functions are left out.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Bool(bool),
+    Integer(i64),
+    BigInt(BigInt),
+    Rational(BigRational),
+    Char(char),
+    String(String),
+    Symbol(String),
+    Keyword(String),
+    List(Vec<Key>),
+}
+
+impl From<&str> for Key {
+    fn from(s: &str) -> Key {
+        Key::String(s.to_string())
+    }
+}
+
+impl From<String> for Key {
+    fn from(s: String) -> Key {
+        Key::String(s)
+    }
+}
+
+impl From<Key> for Expression {
+    fn from(key: Key) -> Expression {
+        match key {
+            Key::Bool(b) => Expression::Bool(b),
+            Key::Integer(i) => Expression::Integer(i),
+            Key::BigInt(n) => Expression::BigInt(n),
+            Key::Rational(r) => Expression::Rational(r),
+            Key::Char(c) => Expression::Char(c),
+            Key::String(s) => Expression::String(s),
+            Key::Symbol(s) => Expression::Symbol(s),
+            Key::Keyword(s) => Expression::Keyword(s),
+            Key::List(keys) => Expression::List(keys.into_iter().map(Expression::from).collect()),
+        }
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Expression::from(self.clone()))
+    }
+}
+
+pub type NativeFn = dyn Fn(&mut Interpreter, &[Expression]) -> Result<Expression>;
+
+/// The closure behind an `Expression::Native`, compared by identity.
+#[derive(Clone)]
+pub struct NativeFunction(pub Rc<NativeFn>);
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NativeFunction({:p})", Rc::as_ptr(&self.0))
+    }
+}
+
+/// How many arguments a builtin accepts, `max` being `None` when it takes
+/// any number of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arity {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    pub const fn exactly(count: usize) -> Arity {
+        Arity {
+            min: count,
+            max: Some(count),
+        }
+    }
+
+    pub const fn at_least(min: usize) -> Arity {
+        Arity { min, max: None }
+    }
+
+    pub const fn between(min: usize, max: usize) -> Arity {
+        Arity {
+            min,
+            max: Some(max),
+        }
+    }
+
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.min && self.max.is_none_or(|max| count <= max)
+    }
+}
+
+impl std::fmt::Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = |count: usize| if count == 1 { "argument" } else { "arguments" };
+
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{max} {}", plural(max)),
+            Some(max) => write!(f, "{} to {max} arguments", self.min),
+            None => write!(f, "at least {} {}", self.min, plural(self.min)),
+        }
+    }
 }
 
 impl Expression {
+    /// Wraps a Rust closure so it can be called like any other builtin.
+    pub fn native(
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Interpreter, &[Expression]) -> Result<Expression> + 'static,
+    ) -> Expression {
+        Expression::Native {
+            name: name.to_string(),
+            arity,
+            function: NativeFunction(Rc::new(function)),
+        }
+    }
+
     pub fn as_i64(&self) -> Result<i64> {
-        if let Expression::Integer(i) = self {
-            Ok(*i)
-        } else {
-            Err(eyre!("Not an integer: {}", self))
+        match self {
+            Expression::Integer(i) => Ok(*i),
+            Expression::BigInt(n) => Err(error(
+                ErrorKind::Arithmetic,
+                format!("Integer too large: {n}"),
+            )),
+            _ => Err(error(ErrorKind::Type, format!("Not an integer: {}", self))),
+        }
+    }
+
+    /// The value of an exact number: an integer of either size or a
+    /// rational.
+    pub(crate) fn rational(&self) -> Option<BigRational> {
+        match self {
+            Expression::Integer(i) => Some(BigRational::from_integer(BigInt::from(*i))),
+            Expression::BigInt(n) => Some(BigRational::from_integer(n.clone())),
+            Expression::Rational(r) => Some(r.clone()),
+            _ => None,
+        }
+    }
+
+    /// The value of any number, as the nearest float.
+    pub(crate) fn number(&self) -> Option<f64> {
+        match self {
+            Expression::Integer(i) => Some(*i as f64),
+            Expression::BigInt(n) => n.to_f64(),
+            Expression::Rational(r) => r.to_f64(),
+            Expression::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// Equality as `=` sees it, where numbers are equal when their values
+    /// are, whatever their types, so `1`, `1.0` and `2/2` are all equal.
+    pub fn equals(&self, other: &Expression) -> bool {
+        match (self, other) {
+            (Expression::List(a), Expression::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.equals(y))
+            }
+            (Expression::Table(a), Expression::Table(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(key, x)| b.get(key).is_some_and(|y| x.equals(y)))
+            }
+            _ if self.number().is_some() && other.number().is_some() => {
+                self.partial_cmp(other) == Some(std::cmp::Ordering::Equal)
+            }
+            _ => self == other,
         }
     }
 
@@ -45,7 +416,7 @@ impl Expression {
         if let Expression::Float(f) = self {
             Ok(*f)
         } else {
-            Err(eyre!("Not a float: {}", self))
+            Err(error(ErrorKind::Type, format!("Not a float: {}", self)))
         }
     }
 
@@ -53,7 +424,15 @@ impl Expression {
         if let Expression::String(s) = self {
             Ok(s.clone())
         } else {
-            Err(eyre!("Not a string: {}", self))
+            Err(error(ErrorKind::Type, format!("Not a string: {}", self)))
+        }
+    }
+
+    pub fn as_char(&self) -> Result<char> {
+        if let Expression::Char(c) = self {
+            Ok(*c)
+        } else {
+            Err(error(ErrorKind::Type, format!("Not a char: {}", self)))
         }
     }
 
@@ -61,44 +440,64 @@ impl Expression {
         if let Expression::Symbol(s) = self {
             Ok(s.clone())
         } else {
-            Err(eyre!("Not a symbol: {}", self))
+            Err(error(ErrorKind::Type, format!("Not a symbol: {}", self)))
         }
     }
 
+    /// Whether the value counts as true in a condition: everything but
+    /// `false` and `nil` does, including `0`, `""` and the empty list.
     pub fn as_boolean(&self) -> Result<bool> {
-        Ok(!matches!(self, Expression::Nil))
+        Ok(!matches!(self, Expression::Nil | Expression::Bool(false)))
     }
 
     pub fn as_list(&self) -> Result<Vec<Expression>> {
         if let Expression::List(l) = self {
-            Ok(l.clone())
+            Ok(l.to_vec())
         } else {
-            Err(eyre!("Not a list: {}", self))
+            Err(error(ErrorKind::Type, format!("Not a list: {}", self)))
         }
     }
 
-    pub fn as_table(&self) -> Result<HashMap<String, Expression>> {
+    pub fn as_key(&self) -> Result<Key> {
+        match self {
+            Expression::Bool(b) => Ok(Key::Bool(*b)),
+            Expression::Integer(i) => Ok(Key::Integer(*i)),
+            Expression::BigInt(n) => Ok(Key::BigInt(n.clone())),
+            Expression::Rational(r) => Ok(Key::Rational(r.clone())),
+            Expression::Char(c) => Ok(Key::Char(*c)),
+            Expression::String(s) => Ok(Key::String(s.clone())),
+            Expression::Symbol(s) => Ok(Key::Symbol(s.clone())),
+            Expression::Keyword(s) => Ok(Key::Keyword(s.clone())),
+            Expression::List(list) => Ok(Key::List(
+                list.iter().map(Expression::as_key).collect::<Result<_>>()?,
+            )),
+            _ => Err(error(ErrorKind::Type, format!("Not a table key: {}", self))),
+        }
+    }
+
+    pub fn as_table(&self) -> Result<Table> {
         if let Expression::Table(t) = self {
             Ok(t.clone())
         } else {
-            Err(eyre!("Not a list: {}", self))
+            Err(error(ErrorKind::Type, format!("Not a table: {}", self)))
         }
     }
 
     pub fn as_type_string(&self) -> String {
         match self {
-            Expression::Builtin {
-                name: _,
-                function: _,
-            } => "builtin".to_string(),
-            Expression::Function {
-                arguments: _,
-                body: _,
-            } => "function".to_string(),
+            Expression::Builtin { .. }
+            | Expression::SpecialForm { .. }
+            | Expression::Native { .. } => "builtin".to_string(),
+            Expression::Function { .. } => "function".to_string(),
+            Expression::Macro { .. } => "macro".to_string(),
             Expression::List(_) => "list".to_string(),
-            Expression::Integer(_) => "integer".to_string(),
+            Expression::Bool(_) => "boolean".to_string(),
+            Expression::Integer(_) | Expression::BigInt(_) => "integer".to_string(),
+            Expression::Rational(_) => "rational".to_string(),
+            Expression::Char(_) => "char".to_string(),
             Expression::String(_) => "string".to_string(),
             Expression::Symbol(_) => "symbol".to_string(),
+            Expression::Keyword(_) => "keyword".to_string(),
             Expression::Nil => "nil".to_string(),
             Expression::Float(_) => "float".to_string(),
             Expression::Table(_) => "table".to_string(),
@@ -107,8 +506,13 @@ impl Expression {
 
     pub fn as_debug_string(&self) -> String {
         match self {
-            Expression::Builtin { name, function: _ } => name.to_string(),
-            Expression::Function { arguments: _, body } => body.as_debug_string(),
+            Expression::Builtin { name, .. } | Expression::SpecialForm { name, .. } => {
+                name.to_string()
+            }
+            Expression::Native { name, .. } => name.clone(),
+            Expression::Function { body, .. } | Expression::Macro { body, .. } => {
+                body.as_debug_string()
+            }
             Expression::List(list) => format!(
                 "({})",
                 list.iter()
@@ -116,10 +520,15 @@ impl Expression {
                     .collect::<Vec<String>>()
                     .join(" ")
             ),
+            Expression::Bool(b) => b.to_string(),
             Expression::Integer(i) => i.to_string(),
+            Expression::BigInt(n) => n.to_string(),
+            Expression::Rational(r) => r.to_string(),
             Expression::Float(f) => f.to_string(),
-            Expression::String(s) => format!("\"{s}\""),
+            Expression::Char(c) => escape_char(*c),
+            Expression::String(s) => escape_string(s),
             Expression::Symbol(s) => s.to_string(),
+            Expression::Keyword(s) => format!(":{s}"),
             Expression::Nil => "nil".to_string(),
             Expression::Table(table) => {
                 if table.is_empty() {
@@ -129,9 +538,9 @@ impl Expression {
                         "{{ {} }}",
                         table
                             .iter()
-                            .map(|(key, value)| format!("{key}: {value}"))
+                            .map(|(key, value)| format!("{key} {value}"))
                             .collect::<Vec<String>>()
-                            .join(", ")
+                            .join(" ")
                     )
                 }
             }
@@ -139,14 +548,56 @@ impl Expression {
     }
 }
 
+/// Quotes `s` using the reader's escape sequences, so the result parses back
+/// to the same string.
+pub fn escape_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+
+    escaped.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\0' => escaped.push_str("\\0"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+
+    escaped
+}
+
+/// Writes `c` as a character literal, by name where it has one.
+pub fn escape_char(c: char) -> String {
+    match c {
+        ' ' => "#\\space".to_string(),
+        '\n' => "#\\newline".to_string(),
+        '\t' => "#\\tab".to_string(),
+        '\r' => "#\\return".to_string(),
+        '\0' => "#\\nul".to_string(),
+        c if c.is_control() || c.is_whitespace() => format!("#\\u{{{:x}}}", c as u32),
+        c => format!("#\\{c}"),
+    }
+}
+
 impl std::fmt::Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
             "{}",
             match self {
-                Expression::Builtin { name, function: _ } => format!("{}", name.yellow()),
-                Expression::Function { arguments, body } => {
+                Expression::Builtin { name, .. } | Expression::SpecialForm { name, .. } =>
+                    format!("{}", name.yellow()),
+                Expression::Native { name, .. } => format!("{}", name.yellow()),
+                Expression::Function {
+                    arguments, body, ..
+                } => {
                     format!(
                         "{} : ({}) => {}",
                         "function".blue(),
@@ -158,6 +609,20 @@ impl std::fmt::Display for Expression {
                         body
                     )
                 }
+                Expression::Macro {
+                    arguments, body, ..
+                } => {
+                    format!(
+                        "{} : ({}) => {}",
+                        "macro".blue(),
+                        arguments
+                            .iter()
+                            .map(|a| a.to_string())
+                            .collect::<Vec<String>>()
+                            .join(" "),
+                        body
+                    )
+                }
                 Expression::List(list) => format!(
                     "({})",
                     list.iter()
@@ -173,15 +638,21 @@ impl std::fmt::Display for Expression {
                             "{{ {} }}",
                             table
                                 .iter()
-                                .map(|(key, value)| format!("{key}: {value}"))
+                                .map(|(key, value)| format!("{key} {value}"))
                                 .collect::<Vec<String>>()
-                                .join(", ")
+                                .join(" ")
                         )
                     },
+                Expression::Bool(b) => b.to_string().purple().to_string(),
                 Expression::Integer(i) => i.to_string().yellow().to_string(),
-                Expression::String(s) => format!("\"{}\"", s).green().to_string(),
+                Expression::BigInt(n) => n.to_string().yellow().to_string(),
+                Expression::Rational(r) => r.to_string().yellow().to_string(),
+                Expression::Char(c) => escape_char(*c).green().to_string(),
+                Expression::String(s) => escape_string(s).green().to_string(),
                 Expression::Symbol(s) => s.to_string(),
+                Expression::Keyword(s) => format!(":{s}").cyan().to_string(),
                 Expression::Nil => "nil".to_string().purple().to_string(),
+                Expression::Float(f) if f.is_nan() => "nan".yellow().to_string(),
                 Expression::Float(f) => format!("{:?}", f).yellow().to_string(),
             }
         )
@@ -190,27 +661,78 @@ impl std::fmt::Display for Expression {
 
 impl From<bool> for Expression {
     fn from(b: bool) -> Self {
-        if b {
-            TRUE.clone()
+        Expression::Bool(b)
+    }
+}
+
+/// Bignums that fit in an `i64` become plain integers.
+impl From<BigInt> for Expression {
+    fn from(n: BigInt) -> Self {
+        match n.to_i64() {
+            Some(i) => Expression::Integer(i),
+            None => Expression::BigInt(n),
+        }
+    }
+}
+
+/// Whole rationals become integers.
+impl From<BigRational> for Expression {
+    fn from(r: BigRational) -> Self {
+        if r.is_integer() {
+            r.to_integer().into()
         } else {
-            NIL.clone()
+            Expression::Rational(r)
         }
     }
 }
 
+/// Combines two numbers: integers and rationals exactly, moving to bignums
+/// rather than overflowing, and anything involving a float as floats.
+/// `checked` is the shortcut for two `i64`s, giving up when the result
+/// isn't one.
+fn arithmetic(
+    a: &Expression,
+    b: &Expression,
+    verb: &str,
+    checked: fn(i64, i64) -> Option<i64>,
+    exact: fn(BigRational, BigRational) -> BigRational,
+    inexact: fn(f64, f64) -> f64,
+) -> Result<Expression> {
+    if let (Expression::Integer(x), Expression::Integer(y)) = (a, b) {
+        if let Some(result) = checked(*x, *y) {
+            return Ok(Expression::Integer(result));
+        }
+    }
+
+    match (a.rational(), b.rational(), a.number(), b.number()) {
+        (Some(x), Some(y), _, _) => Ok(exact(x, y).into()),
+        (_, _, Some(x), Some(y)) => Ok(Expression::Float(inexact(x, y))),
+        _ => Err(error(ErrorKind::Type, format!("Can't {verb} {a} and {b}"))),
+    }
+}
+
+/// Fails when `divisor` is an integer or float zero, so dividing by zero is an
+/// error rather than a float infinity or NaN.
+fn check_divisor(divisor: &Expression) -> Result<()> {
+    match divisor {
+        Expression::Integer(0) => Err(error(ErrorKind::Arithmetic, "Division by zero")),
+        Expression::Float(f) if *f == 0.0 => Err(error(ErrorKind::Arithmetic, "Division by zero")),
+        _ => Ok(()),
+    }
+}
+
 impl std::ops::Add for Expression {
     type Output = Result<Expression>;
 
     fn add(self, rhs: Self) -> Self::Output {
-        match (&self, &rhs) {
-            (Expression::Integer(a), Expression::Integer(b)) => Ok(Expression::Integer(a + b)),
-            (Expression::Float(a), Expression::Float(b)) => Ok(Expression::Float(a + b)),
-            (Expression::Integer(a), Expression::Float(b))
-            | (Expression::Float(b), Expression::Integer(a)) => {
-                Ok(Expression::Float(*a as f64 + b))
-            }
-            _ => Err(eyre!("Can't add {} and {}", self, rhs)),
-        }
+        arithmetic(
+            &self,
+            &rhs,
+            "add",
+            i64::checked_add,
+            |a, b| a + b,
+            |a, b| a + b,
+        )
     }
 }
 
@@ -218,15 +740,14 @@ impl std::ops::Sub for Expression {
     type Output = Result<Expression>;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        match (&self, &rhs) {
-            (Expression::Integer(a), Expression::Integer(b)) => Ok(Expression::Integer(a - b)),
-            (Expression::Float(a), Expression::Float(b)) => Ok(Expression::Float(a - b)),
-            (Expression::Integer(a), Expression::Float(b))
-            | (Expression::Float(b), Expression::Integer(a)) => {
-                Ok(Expression::Float(*a as f64 - b))
-            }
-            _ => Err(eyre!("Can't subtract {} and {}", self, rhs)),
-        }
+        arithmetic(
+            &self,
+            &rhs,
+            "subtract",
+            i64::checked_sub,
+            |a, b| a - b,
+            |a, b| a - b,
+        )
     }
 }
 
@@ -234,15 +755,14 @@ impl std::ops::Mul for Expression {
     type Output = Result<Expression>;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        match (&self, &rhs) {
-            (Expression::Integer(a), Expression::Integer(b)) => Ok(Expression::Integer(a * b)),
-            (Expression::Float(a), Expression::Float(b)) => Ok(Expression::Float(a * b)),
-            (Expression::Integer(a), Expression::Float(b))
-            | (Expression::Float(b), Expression::Integer(a)) => {
-                Ok(Expression::Float(*a as f64 * b))
-            }
-            _ => Err(eyre!("Can't multiply {} and {}", self, rhs)),
-        }
+        arithmetic(
+            &self,
+            &rhs,
+            "multiply",
+            i64::checked_mul,
+            |a, b| a * b,
+            |a, b| a * b,
+        )
     }
 }
 
@@ -250,42 +770,56 @@ impl std::ops::Div for Expression {
     type Output = Result<Expression>;
 
     fn div(self, rhs: Self) -> Self::Output {
-        match (&self, &rhs) {
-            (Expression::Integer(a), Expression::Integer(b)) => {
-                if *b == 0 {
-                    Err(eyre!("Division by zero"))
-                } else {
-                    Ok(Expression::Integer(a / b))
-                }
-            }
-            (Expression::Float(a), Expression::Float(b)) => {
-                if *b == 0.0 {
-                    Err(eyre!("Division by zero"))
-                } else {
-                    Ok(Expression::Float(a / b))
-                }
-            }
-            (Expression::Integer(a), Expression::Float(b))
-            | (Expression::Float(b), Expression::Integer(a)) => {
-                if *b == 0.0 {
-                    Err(eyre!("Division by zero"))
-                } else {
-                    Ok(Expression::Float(*a as f64 / b))
-                }
-            }
-            _ => Err(eyre!("Can't divide {} and {}", self, rhs)),
-        }
+        check_divisor(&rhs)?;
+
+        arithmetic(
+            &self,
+            &rhs,
+            "divide",
+            |a, b| match a.checked_rem(b)? {
+                0 => a.checked_div(b),
+                _ => None,
+            },
+            |a, b| a / b,
+            |a, b| a / b,
+        )
+    }
+}
+
+impl std::ops::Rem for Expression {
+    type Output = Result<Expression>;
+
+    fn rem(self, rhs: Self) -> Self::Output {
+        check_divisor(&rhs)?;
+
+        arithmetic(
+            &self,
+            &rhs,
+            "divide",
+            i64::checked_rem,
+            |a, b| a % b,
+            |a, b| a % b,
+        )
     }
 }
 
+/// Numbers compare by their exact values, floats included, so `(< 1 1.5)`
+/// holds and a float never equals an integer it merely rounds to.
 impl std::cmp::PartialOrd for Expression {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        let exact = |e: &Expression| match e {
+            Expression::Float(f) => BigRational::from_float(*f),
+            _ => e.rational(),
+        };
+
         match (self, other) {
             (Expression::Integer(a), Expression::Integer(b)) => Some(a.cmp(b)),
-            (Expression::Float(a), Expression::Float(b)) => Some(a.partial_cmp(b)?),
-            (Expression::Integer(a), Expression::Float(b))
-            | (Expression::Float(b), Expression::Integer(a)) => Some(a.partial_cmp(&(*b as i64))?),
-            _ => None,
+            (Expression::Float(a), Expression::Float(b)) => a.partial_cmp(b),
+            _ => match (exact(self), exact(other)) {
+                (Some(a), Some(b)) => Some(a.cmp(&b)),
+                // Infinities and NaN, which have no exact value
+                _ => self.number()?.partial_cmp(&other.number()?),
+            },
         }
     }
 }
@@ -0,0 +1,131 @@
+use color_eyre::{eyre::eyre, Result};
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use crate::{
+    builtin::std_lib,
+    env::Env,
+    eval::{eval_list, quoted},
+    expression::{Arity, Expression},
+    run_source,
+};
+
+/// An Arcanya interpreter, owning the global environment its programs run in.
+///
+/// ```
+/// use arcanya::{expression::Expression, Interpreter};
+///
+/// let mut interpreter = Interpreter::new();
+///
+/// interpreter.eval_str("(define 'double (function '(x) '(* x 2)))").unwrap();
+///
+/// assert_eq!(
+///     interpreter.call("double", &[Expression::Integer(21)]).unwrap(),
+///     Expression::Integer(42)
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Interpreter {
+    env: Rc<RefCell<Env>>,
+}
+
+impl Interpreter {
+    /// An interpreter with the standard library in scope.
+    pub fn new() -> Interpreter {
+        Interpreter::with_env(std_lib())
+    }
+
+    pub fn with_env(env: Env) -> Interpreter {
+        Interpreter {
+            env: Rc::new(RefCell::new(env)),
+        }
+    }
+
+    /// A handle on `env`, as given to native functions called from it.
+    pub(crate) fn from_shared(env: Rc<RefCell<Env>>) -> Interpreter {
+        Interpreter { env }
+    }
+
+    pub fn env(&self) -> &Rc<RefCell<Env>> {
+        &self.env
+    }
+
+    pub fn eval_str(&mut self, input: &str) -> Result<Expression> {
+        self.eval_source("<input>", input)
+    }
+
+    /// Like `eval_str`, with errors pointing into a source called `name`.
+    pub fn eval_source(&mut self, name: &str, input: &str) -> Result<Expression> {
+        run_source(&mut self.env, name, input)
+    }
+
+    pub fn eval_file(&mut self, path: impl AsRef<Path>) -> Result<Expression> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+
+        self.eval_source(&path.display().to_string(), &content)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Expression> {
+        self.env.borrow().get(name)
+    }
+
+    /// Defines `name` globally, as `define` would.
+    pub fn set(&mut self, name: &str, value: Expression) {
+        self.env.borrow_mut().set_global(name.to_string(), value);
+    }
+
+    /// Makes a Rust closure callable from Arcanya as `name`.
+    ///
+    /// ```
+    /// use arcanya::{expression::{Arity, Expression}, Interpreter};
+    ///
+    /// let mut interpreter = Interpreter::new();
+    /// let greeting = String::from("hello");
+    ///
+    /// interpreter.register("greet", Arity::exactly(1), move |_, arguments| {
+    ///     Ok(Expression::String(format!("{greeting} {}", arguments[0].as_string()?)))
+    /// });
+    ///
+    /// assert_eq!(
+    ///     interpreter.eval_str("(greet \"world\")").unwrap(),
+    ///     Expression::String("hello world".into())
+    /// );
+    /// ```
+    pub fn register(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Interpreter, &[Expression]) -> Result<Expression> + 'static,
+    ) {
+        self.env.borrow_mut().register(name, arity, function);
+    }
+
+    /// Calls the function bound to `name` with already evaluated `arguments`.
+    pub fn call(&mut self, name: &str, arguments: &[Expression]) -> Result<Expression> {
+        let function = self
+            .get(name)
+            .ok_or_else(|| eyre!("Undefined function: {name}"))?;
+
+        self.call_value(&function, arguments)
+    }
+
+    /// Like `call`, for a function at hand, such as one passed to a native
+    /// function.
+    pub fn call_value(
+        &mut self,
+        function: &Expression,
+        arguments: &[Expression],
+    ) -> Result<Expression> {
+        let call = std::iter::once(function.clone())
+            .chain(arguments.iter().cloned().map(quoted))
+            .collect::<Vec<Expression>>();
+
+        eval_list(&mut self.env, &call)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
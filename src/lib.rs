@@ -0,0 +1,79 @@
+use color_eyre::Result;
+use std::sync::atomic::Ordering;
+use std::{cell::RefCell, rc::Rc};
+
+pub mod builtin;
+pub mod convert;
+pub mod env;
+pub mod error;
+pub mod eval;
+pub mod expression;
+pub mod interpreter;
+pub mod parse;
+pub mod serialize;
+pub mod source;
+
+#[cfg(test)]
+mod tests;
+
+// Lets code derived with `arcanya_derive` refer to this crate as `::arcanya`.
+extern crate self as arcanya;
+
+pub use interpreter::Interpreter;
+
+use env::Env;
+use error::ErrorKind;
+use eval::{eval_expression, expand, EVALUATION_COUNT, LAST_EVALUATION_COUNT};
+use expression::Expression;
+use parse::{parse_expression, parse_program};
+use source::{Source, Span};
+
+pub fn run(env: &mut Rc<RefCell<Env>>, input: &str) -> Result<Expression> {
+    run_source(env, "<input>", input)
+}
+
+/// Evaluates every form of `input` in order, returning the last value.
+/// `name` is used when pointing at the source of an error.
+pub fn run_source(env: &mut Rc<RefCell<Env>>, name: &str, input: &str) -> Result<Expression> {
+    let forms = match parse_program(name, input) {
+        Ok(forms) => forms,
+        Err(error) => {
+            let source = Source::new(name, input);
+
+            return Err(source::located(
+                error::error(ErrorKind::Parse, format!("Parse error: {}", error.message)),
+                source.excerpt(Span {
+                    offset: error.offset,
+                    length: input[error.offset..]
+                        .chars()
+                        .next()
+                        .map_or(1, char::len_utf8),
+                }),
+            ));
+        }
+    };
+
+    let mut result = Expression::Nil;
+
+    for form in forms {
+        result = eval_expression(env, &expand(env, &form)?)?;
+    }
+
+    Ok(result)
+}
+
+#[allow(dead_code)]
+fn run_log(env: &mut Rc<RefCell<Env>>, input: &str) -> Result<Expression> {
+    let value = parse_expression(input)
+        .map(|(_, expr)| eval_expression(env, &expr))
+        .map_err(|e| e.to_owned())?;
+
+    println!(
+        "Evaluation count: {}",
+        EVALUATION_COUNT.load(Ordering::SeqCst) - LAST_EVALUATION_COUNT.load(Ordering::SeqCst)
+    );
+
+    LAST_EVALUATION_COUNT.store(EVALUATION_COUNT.load(Ordering::SeqCst), Ordering::SeqCst);
+
+    value
+}
@@ -1,34 +1,16 @@
+use arcanya::Interpreter;
 use color_eyre::Result;
 use std::io::{BufRead, Write};
-use std::sync::atomic::Ordering;
-use std::{cell::RefCell, rc::Rc};
-
-pub mod builtin;
-pub mod env;
-pub mod eval;
-pub mod expression;
-pub mod parse;
-
-#[cfg(test)]
-mod tests;
-
-use env::Env;
-use eval::{eval_expression, EVALUATION_COUNT, LAST_EVALUATION_COUNT};
-use expression::Expression;
-use parse::parse_expression;
 
 fn main() -> Result<()> {
     color_eyre::install()?;
 
-    let mut global = Rc::new(RefCell::new(builtin::std_lib()));
+    let mut interpreter = Interpreter::new();
 
     let file_path = std::env::args().nth(1);
 
     if let Some(file_path) = file_path {
-        let content = std::fs::read_to_string(file_path)?;
-        let content = content.trim();
-
-        let returned = run(&mut global, &content);
+        let returned = interpreter.eval_file(&file_path);
 
         match returned {
             Ok(value) => println!("=> {}", value),
@@ -49,9 +31,11 @@ fn main() -> Result<()> {
             let stdin = std::io::stdin();
             let mut handle = stdin.lock();
 
-            handle.read_line(&mut buffer)?;
+            if handle.read_line(&mut buffer)? == 0 {
+                return Ok(());
+            }
 
-            let returned = run(&mut global, &buffer);
+            let returned = interpreter.eval_source("<repl>", &buffer);
 
             match returned {
                 Ok(value) => println!("=> {}", value),
@@ -60,35 +44,3 @@ fn main() -> Result<()> {
         }
     }
 }
-
-pub fn run(env: &mut Rc<RefCell<Env>>, input: &str) -> Result<Expression> {
-    match parse_expression(input) {
-        Ok((rest, expr)) => {
-            let result = eval_expression(env, &expr);
-            let rest = rest.trim();
-
-            if !rest.is_empty() {
-                run(env, rest)
-            } else {
-                result
-            }
-        }
-        Err(e) => Err(e.to_owned())?,
-    }
-}
-
-#[allow(dead_code)]
-fn run_log(env: &mut Rc<RefCell<Env>>, input: &str) -> Result<Expression> {
-    let value = parse_expression(input)
-        .map(|(_, expr)| eval_expression(env, &expr))
-        .map_err(|e| e.to_owned())?;
-
-    println!(
-        "Evaluation count: {}",
-        EVALUATION_COUNT.load(Ordering::SeqCst) - LAST_EVALUATION_COUNT.load(Ordering::SeqCst)
-    );
-
-    LAST_EVALUATION_COUNT.store(EVALUATION_COUNT.load(Ordering::SeqCst), Ordering::SeqCst);
-
-    value
-}
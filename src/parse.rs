@@ -1,28 +1,110 @@
 use nom::{
     branch::alt,
-    bytes::complete::{is_not, tag},
-    character::complete::{alpha1, alphanumeric1, char, digit1, multispace0, multispace1},
-    combinator::{cut, map, map_res, opt, recognize},
-    multi::{many0_count, separated_list0},
-    sequence::{delimited, pair, preceded, separated_pair, tuple},
+    bytes::complete::{
+        is_not, tag, tag_no_case, take_till, take_until, take_while, take_while1, take_while_m_n,
+    },
+    character::complete::{
+        alpha1, alphanumeric1, anychar, char, digit1, multispace1, one_of, satisfy,
+    },
+    combinator::{cut, map, map_opt, map_res, not, opt, recognize, value},
+    error::{Error, ErrorKind},
+    multi::{fold_many0, many0_count, many1_count, separated_list0},
+    sequence::{delimited, pair, preceded, terminated, tuple},
     IResult,
 };
 
-use crate::expression::Expression;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    expression::{Expression, List, Table},
+    source::{Location, Source, Span},
+};
+
+thread_local! {
+    /// The source `parse_program` is reading, for lists to point into.
+    static READING: RefCell<Option<Rc<Source>>> = const { RefCell::new(None) };
+}
+
+fn line_comment(input: &str) -> IResult<&str, &str> {
+    recognize(pair(char(';'), take_till(|c| c == '\n')))(input)
+}
+
+fn block_comment(input: &str) -> IResult<&str, &str> {
+    let (mut rest, _) = tag("#|")(input)?;
+    let mut depth = 1;
+
+    while depth > 0 {
+        if let Some(after) = rest.strip_prefix("|#") {
+            depth -= 1;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("#|") {
+            depth += 1;
+            rest = after;
+        } else {
+            let mut chars = rest.chars();
+
+            if chars.next().is_none() {
+                return Err(nom::Err::Failure(Error::new(input, ErrorKind::TakeUntil)));
+            }
+
+            rest = chars.as_str();
+        }
+    }
+
+    Ok((rest, &input[..input.len() - rest.len()]))
+}
+
+fn datum_comment(input: &str) -> IResult<&str, &str> {
+    recognize(pair(tag("#;"), cut(parse_expression)))(input)
+}
+
+fn comment(input: &str) -> IResult<&str, &str> {
+    alt((line_comment, block_comment, datum_comment))(input)
+}
+
+/// Optional whitespace, where comments count as whitespace.
+pub fn whitespace0(input: &str) -> IResult<&str, &str> {
+    recognize(many0_count(alt((multispace1, comment))))(input)
+}
+
+/// Required whitespace, where comments count as whitespace.
+pub fn whitespace1(input: &str) -> IResult<&str, &str> {
+    recognize(many1_count(alt((multispace1, comment))))(input)
+}
+
+/// Whether a symbol could go on after `c`, so `true-count` isn't read as
+/// `true` followed by `-count`.
+fn is_symbol_char(c: char) -> bool {
+    c.is_alphanumeric() || "_+-/%*=><&".contains(c)
+}
+
+/// `word` as a whole word, not the start of a longer symbol.
+fn word<'a>(word: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    terminated(tag(word), not(satisfy(is_symbol_char)))
+}
 
 fn parse_bool(input: &str) -> IResult<&str, Expression> {
     map(
-        alt((tag("true"), tag("false"), tag("#t"), tag("#f"), tag("nil"))),
+        alt((
+            word("true"),
+            word("false"),
+            tag("#t"),
+            tag("#f"),
+            word("nil"),
+        )),
         |s: &str| match s {
-            "true" | "#t" => true.into(),
-            "false" | "#f" | "nil" => false.into(),
+            "true" | "#t" => Expression::Bool(true),
+            "false" | "#f" => Expression::Bool(false),
+            "nil" => Expression::Nil,
             _ => unreachable!(),
         },
     )(input)
 }
 
 fn parse_void(input: &str) -> IResult<&str, Expression> {
-    map(tag("void"), |_| Expression::Nil)(input)
+    map(word("void"), |_| Expression::Nil)(input)
 }
 
 fn parse_symbol(input: &str) -> IResult<&str, Expression> {
@@ -39,6 +121,7 @@ fn parse_symbol(input: &str) -> IResult<&str, Expression> {
                 tag("="),
                 tag(">"),
                 tag("<"),
+                tag("&"),
             )),
             many0_count(alt((
                 alphanumeric1,
@@ -51,85 +134,347 @@ fn parse_symbol(input: &str) -> IResult<&str, Expression> {
                 tag("="),
                 tag(">"),
                 tag("<"),
+                tag("&"),
             ))),
         ))),
         |s: &str| Expression::Symbol(s.to_string()),
     )(input)
 }
 
+/// Keywords, `:name`, are named like symbols.
+fn parse_keyword(input: &str) -> IResult<&str, Expression> {
+    map(preceded(char(':'), parse_symbol), |symbol| match symbol {
+        Expression::Symbol(name) => Expression::Keyword(name),
+        _ => unreachable!(),
+    })(input)
+}
+
+/// Characters, `#\a`, written by name for `#\space`, `#\newline`, `#\tab`,
+/// `#\return` and `#\nul`, or by code point as `#\u{3bb}`.
+fn parse_char(input: &str) -> IResult<&str, Expression> {
+    map(
+        preceded(
+            tag("#\\"),
+            cut(alt((
+                value(' ', word("space")),
+                value('\n', word("newline")),
+                value('\t', word("tab")),
+                value('\r', word("return")),
+                value('\0', word("nul")),
+                terminated(parse_unicode_escape, not(satisfy(is_symbol_char))),
+                terminated(anychar, not(satisfy(is_symbol_char))),
+            ))),
+        ),
+        Expression::Char,
+    )(input)
+}
+
+/// Digits in `radix`, optionally grouped by single underscores, as in
+/// `1_000_000`. The underscores are left out of the result.
+fn digits<'a>(radix: u32) -> impl FnMut(&'a str) -> IResult<&'a str, String> {
+    let digit = move |c: char| c.is_digit(radix);
+
+    map(
+        recognize(pair(
+            take_while1(digit),
+            many0_count(pair(char('_'), take_while1(digit))),
+        )),
+        |digits: &str| digits.replace('_', ""),
+    )
+}
+
+fn sign(input: &str) -> IResult<&str, Option<char>> {
+    opt(one_of("+-"))(input)
+}
+
+fn exponent(input: &str) -> IResult<&str, &str> {
+    recognize(tuple((one_of("eE"), sign, digit1)))(input)
+}
+
+/// Floats, written with a decimal point or an exponent or both (`1.5`, `1.`,
+/// `.5`, `1e10`, `2.5E-3`), and `inf`, `-inf` and `nan`.
 pub fn parse_float(input: &str) -> IResult<&str, Expression> {
     alt((
-        map(
-            map_res(separated_pair(digit1, tag("."), opt(digit1)), |(a, b)| {
-                format!("{}.{}", a, b.unwrap_or("0")).parse::<f64>()
-            }),
-            |f: f64| Expression::Float(f),
-        ),
         map(
             map_res(
-                preceded(tag("-"), separated_pair(digit1, tag("."), opt(digit1))),
-                |(a, b)| format!("{}.{}", a, b.unwrap_or("0")).parse::<f64>(),
+                recognize(pair(
+                    sign,
+                    alt((
+                        recognize(tuple((
+                            digits(10),
+                            char('.'),
+                            opt(digits(10)),
+                            opt(exponent),
+                        ))),
+                        recognize(tuple((char('.'), digits(10), opt(exponent)))),
+                        recognize(pair(digits(10), exponent)),
+                    )),
+                )),
+                |text: &str| text.replace('_', "").parse::<f64>(),
             ),
-            |f: f64| Expression::Float(-f),
+            Expression::Float,
         ),
+        map(pair(sign, word("inf")), |(sign, _)| {
+            Expression::Float(match sign {
+                Some('-') => f64::NEG_INFINITY,
+                _ => f64::INFINITY,
+            })
+        }),
+        map(word("nan"), |_| Expression::Float(f64::NAN)),
     ))(input)
 }
 
+/// Exact fractions, `1/3`, read in lowest terms. A zero denominator is an
+/// error rather than an integer followed by a symbol.
+pub fn parse_rational(input: &str) -> IResult<&str, Expression> {
+    let (rest, (sign, numerator, _, denominator)) =
+        tuple((sign, digits(10), char('/'), digits(10)))(input)?;
+    let text = format!("{}{numerator}/{denominator}", sign.unwrap_or('+'));
+
+    match text.parse::<BigRational>() {
+        Ok(rational) => Ok((rest, rational.into())),
+        Err(_) => Err(nom::Err::Failure(Error::new(input, ErrorKind::MapRes))),
+    }
+}
+
+/// Integers of any length, those too large for an `i64` read as bignums.
+/// They can be written in hexadecimal (`0xff`), octal (`0o17`) or binary
+/// (`0b1010`) as well as in decimal, all with an optional sign.
 pub fn parse_integer(input: &str) -> IResult<&str, Expression> {
-    alt((
-        map(
-            map_res(digit1, |digit_str: &str| digit_str.parse::<i64>()),
-            |i: i64| Expression::Integer(i),
+    map_opt(
+        pair(
+            sign,
+            alt((
+                map(preceded(tag_no_case("0x"), digits(16)), |d| (d, 16)),
+                map(preceded(tag_no_case("0o"), digits(8)), |d| (d, 8)),
+                map(preceded(tag_no_case("0b"), digits(2)), |d| (d, 2)),
+                map(digits(10), |d| (d, 10)),
+            )),
         ),
-        map(
-            map_res(preceded(tag("-"), digit1), |digit_str: &str| {
-                digit_str.parse::<i64>()
-            }),
-            |i: i64| Expression::Integer(-i),
+        |(sign, (digits, radix))| {
+            let n = BigInt::parse_bytes(digits.as_bytes(), radix)?;
+
+            Some(Expression::from(if sign == Some('-') { -n } else { n }))
+        },
+    )(input)
+}
+
+/// Any number, which can't run straight into a symbol: `1abc` is an error,
+/// not `1` followed by `abc`.
+pub fn parse_number(input: &str) -> IResult<&str, Expression> {
+    terminated(
+        alt((parse_float, parse_rational, parse_integer)),
+        not(satisfy(is_symbol_char)),
+    )(input)
+}
+
+fn parse_unicode_escape(input: &str) -> IResult<&str, char> {
+    map_opt(
+        map_res(
+            delimited(
+                tag("u{"),
+                take_while_m_n(1, 6, |c: char| c.is_ascii_hexdigit()),
+                char('}'),
+            ),
+            |hex| u32::from_str_radix(hex, 16),
         ),
-    ))(input)
+        char::from_u32,
+    )(input)
+}
+
+fn parse_escaped_char(input: &str) -> IResult<&str, char> {
+    preceded(
+        char('\\'),
+        cut(alt((
+            parse_unicode_escape,
+            value('\n', char('n')),
+            value('\r', char('r')),
+            value('\t', char('t')),
+            value('\0', char('0')),
+            value('\\', char('\\')),
+            value('"', char('"')),
+        ))),
+    )(input)
 }
 
 pub fn parse_string(input: &str) -> IResult<&str, Expression> {
-    alt((
-        map(delimited(char('"'), is_not("\""), char('"')), |s: &str| {
-            Expression::String(s.to_string())
-        }),
-        map(pair(tag("\""), tag("\"")), |_| {
-            Expression::String("".to_string())
-        }),
-    ))(input)
+    map(
+        delimited(
+            char('"'),
+            fold_many0(
+                alt((
+                    map(is_not("\"\\"), str::to_string),
+                    map(parse_escaped_char, String::from),
+                )),
+                String::new,
+                |mut string, fragment| {
+                    string.push_str(&fragment);
+                    string
+                },
+            ),
+            cut(char('"')),
+        ),
+        Expression::String,
+    )(input)
+}
+
+/// Raw strings, `#r"..."#`, take their content verbatim. Extra `#`s after
+/// the `r` must be matched before the closing `#`, so `#r#"say "#hi""##`
+/// can contain `"#`.
+pub fn parse_raw_string(input: &str) -> IResult<&str, Expression> {
+    let (rest, hashes) = delimited(tag("#r"), take_while(|c| c == '#'), char('"'))(input)?;
+    let terminator = format!("\"#{hashes}");
+
+    let (rest, content) = cut(take_until(terminator.as_str()))(rest)?;
+
+    Ok((
+        &rest[terminator.len()..],
+        Expression::String(content.to_string()),
+    ))
 }
 
 pub fn parse_list(input: &str) -> IResult<&str, Expression> {
-    delimited(
+    let (rest, items) = delimited(
         char('('),
-        map(
-            separated_list0(multispace1, parse_expression),
-            Expression::List,
+        separated_list0(whitespace1, parse_expression),
+        cut(preceded(whitespace0, char(')'))),
+    )(input)?;
+
+    Ok((rest, Expression::List(located(items, input, rest))))
+}
+
+/// Table literals, `{ key value ... }`, read as a table of the keys and
+/// values as written, the way a quoted list is read as its items.
+pub fn parse_table(input: &str) -> IResult<&str, Expression> {
+    let (mut rest, _) = char('{')(input)?;
+    let mut table = Table::new();
+
+    loop {
+        let (after, _) = whitespace0(rest)?;
+
+        if let Some(after) = after.strip_prefix('}') {
+            return Ok((after, Expression::Table(table)));
+        }
+
+        let (after_key, key) = cut(parse_expression)(after)?;
+        let key = key
+            .as_key()
+            .map_err(|_| nom::Err::Failure(Error::new(after, ErrorKind::MapRes)))?;
+        let (after_value, value) = cut(parse_expression)(after_key)?;
+
+        table.insert(key, value);
+        rest = after_value;
+    }
+}
+
+/// A list of `items` read from `input` up to `rest`, located in the source
+/// being read, if any.
+fn located(items: Vec<Expression>, input: &str, rest: &str) -> List {
+    READING.with(|reading| match &*reading.borrow() {
+        Some(source) => List::located(
+            items,
+            Location {
+                source: source.clone(),
+                span: Span {
+                    offset: source.text.len() - input.len(),
+                    length: input.len() - rest.len(),
+                },
+            },
         ),
-        cut(preceded(multispace0, char(')'))),
-    )(input)
+        None => List::new(items),
+    })
 }
 
 pub fn parse_quoted(input: &str) -> IResult<&str, Expression> {
     map(preceded(char('\''), parse_expression), |list| {
-        Expression::List(vec![Expression::Symbol("quote".to_string()), list])
+        Expression::List(vec![Expression::Symbol("quote".to_string()), list].into())
     })(input)
 }
 
+pub fn parse_quasiquoted(input: &str) -> IResult<&str, Expression> {
+    map(preceded(char('`'), parse_expression), |list| {
+        Expression::List(vec![Expression::Symbol("quasiquote".to_string()), list].into())
+    })(input)
+}
+
+pub fn parse_unquoted(input: &str) -> IResult<&str, Expression> {
+    alt((
+        map(preceded(tag(",@"), parse_expression), |list| {
+            Expression::List(vec![Expression::Symbol("unquote-splicing".to_string()), list].into())
+        }),
+        map(preceded(char(','), parse_expression), |list| {
+            Expression::List(vec![Expression::Symbol("unquote".to_string()), list].into())
+        }),
+    ))(input)
+}
+
 pub fn parse_expression(input: &str) -> IResult<&str, Expression> {
     preceded(
-        multispace0,
+        whitespace0,
         alt((
             parse_quoted,
-            parse_float,
-            parse_integer,
+            parse_quasiquoted,
+            parse_unquoted,
+            parse_number,
             parse_bool,
             parse_void,
             parse_symbol,
+            parse_keyword,
             parse_string,
+            parse_raw_string,
+            parse_char,
             parse_list,
+            parse_table,
         )),
     )(input)
 }
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub offset: usize,
+}
+
+/// Reads every top level form of `input`, with its lists located in a
+/// source called `name`.
+pub fn parse_program(name: &str, input: &str) -> Result<Vec<Expression>, ParseError> {
+    let source = Rc::new(Source::new(name, input));
+
+    READING.with(|reading| *reading.borrow_mut() = Some(source.clone()));
+
+    let mut forms = vec![];
+    let mut rest = source.text.as_str();
+
+    let result = loop {
+        match whitespace0(rest) {
+            Ok(("", _)) => break Ok(()),
+            Ok((after, _)) => rest = after,
+            Err(e) => break Err(e),
+        }
+
+        match parse_expression(rest) {
+            Ok((after, form)) => {
+                forms.push(form);
+                rest = after;
+            }
+            Err(e) => break Err(e),
+        }
+    };
+
+    READING.with(|reading| *reading.borrow_mut() = None);
+
+    match result {
+        Ok(()) => Ok(forms),
+        Err(nom::Err::Error(e) | nom::Err::Failure(e)) => Err(ParseError {
+            message: match e.input.chars().next() {
+                Some(c) => format!("Unexpected {c:?}"),
+                None => "Unexpected end of input".to_string(),
+            },
+            offset: input.len() - e.input.len(),
+        }),
+        Err(nom::Err::Incomplete(_)) => Err(ParseError {
+            message: "Unexpected end of input".to_string(),
+            offset: input.len(),
+        }),
+    }
+}
@@ -0,0 +1,847 @@
+//! Serde support: `Expression` implements `Serialize` and `Deserialize`, and
+//! `to_expression`/`from_expression` convert any serde type through it.
+//!
+//! Lists are sequences, tables are maps, booleans are booleans, rationals
+//! are written as floats and `nil` is the unit value or `None`. Enums use
+//! serde's usual external tagging, a unit variant being its name and any
+//! other variant a table of one entry from its name to its data.
+
+use color_eyre::{Report, Result};
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use serde::{
+    de::{
+        self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess,
+        SeqAccess, VariantAccess, Visitor,
+    },
+    forward_to_deserialize_any,
+    ser::{self, SerializeMap, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    convert::FromExpression,
+    error::{Error, ErrorKind},
+    expression::{Expression, Key, Table},
+};
+
+/// Converts any serializable value into an `Expression`.
+pub fn to_expression<T: Serialize + ?Sized>(value: &T) -> Result<Expression> {
+    value.serialize(ExpressionSerializer).map_err(Report::new)
+}
+
+/// Decodes an `Expression`, usually a table or list, into a Rust value.
+pub fn from_expression<T: DeserializeOwned>(expression: &Expression) -> Result<T> {
+    T::deserialize(expression).map_err(Report::new)
+}
+
+impl ser::Error for Error {
+    fn custom<T: std::fmt::Display>(message: T) -> Self {
+        Error {
+            kind: ErrorKind::Type,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: std::fmt::Display>(message: T) -> Self {
+        Error {
+            kind: ErrorKind::Type,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Serialize for Expression {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Expression::Bool(b) => serializer.serialize_bool(*b),
+            Expression::Integer(i) => serializer.serialize_i64(*i),
+            Expression::BigInt(n) => serialize_bigint(n, serializer),
+            Expression::Rational(r) => serializer.serialize_f64(r.to_f64().unwrap_or(f64::NAN)),
+            Expression::Float(f) => serializer.serialize_f64(*f),
+            Expression::Char(c) => serializer.serialize_char(*c),
+            Expression::String(s) | Expression::Symbol(s) | Expression::Keyword(s) => {
+                serializer.serialize_str(s)
+            }
+            Expression::List(list) => {
+                let mut seq = serializer.serialize_seq(Some(list.len()))?;
+
+                for item in list {
+                    seq.serialize_element(item)?;
+                }
+
+                seq.end()
+            }
+            Expression::Table(table) => {
+                let mut map = serializer.serialize_map(Some(table.len()))?;
+
+                for (key, value) in table {
+                    map.serialize_entry(key, value)?;
+                }
+
+                map.end()
+            }
+            Expression::Nil => serializer.serialize_unit(),
+            _ => Err(ser::Error::custom(format!(
+                "Can't serialize a {}",
+                self.as_type_string()
+            ))),
+        }
+    }
+}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Key::Bool(b) => serializer.serialize_bool(*b),
+            Key::Integer(i) => serializer.serialize_i64(*i),
+            Key::BigInt(n) => serialize_bigint(n, serializer),
+            Key::Rational(r) => serializer.serialize_f64(r.to_f64().unwrap_or(f64::NAN)),
+            Key::Char(c) => serializer.serialize_char(*c),
+            Key::String(s) | Key::Symbol(s) | Key::Keyword(s) => serializer.serialize_str(s),
+            Key::List(keys) => {
+                let mut seq = serializer.serialize_seq(Some(keys.len()))?;
+
+                for key in keys {
+                    seq.serialize_element(key)?;
+                }
+
+                seq.end()
+            }
+        }
+    }
+}
+
+/// Bignums go out as 128 bit integers, which is as far as serde reaches.
+fn serialize_bigint<S: Serializer>(
+    n: &BigInt,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match (n.to_i128(), n.to_u128()) {
+        (Some(i), _) => serializer.serialize_i128(i),
+        (None, Some(u)) => serializer.serialize_u128(u),
+        (None, None) => Err(ser::Error::custom(format!("{n} is too large to serialize"))),
+    }
+}
+
+/// Offers bignums that fit as a `u64` first, the widest type most visitors
+/// take.
+fn deserialize_bigint<'de, V: Visitor<'de>>(
+    n: &BigInt,
+    visitor: V,
+) -> std::result::Result<V::Value, Error> {
+    if let Some(u) = n.to_u64() {
+        return visitor.visit_u64(u);
+    }
+
+    match (n.to_i128(), n.to_u128()) {
+        (Some(i), _) => visitor.visit_i128(i),
+        (None, Some(u)) => visitor.visit_u128(u),
+        (None, None) => Err(de::Error::custom(format!(
+            "{n} is too large to deserialize"
+        ))),
+    }
+}
+
+impl<'de> Deserialize<'de> for Expression {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_any(ExpressionVisitor)
+    }
+}
+
+struct ExpressionVisitor;
+
+impl<'de> Visitor<'de> for ExpressionVisitor {
+    type Value = Expression;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "any value representable in Arcanya")
+    }
+
+    fn visit_bool<E>(self, b: bool) -> std::result::Result<Expression, E> {
+        Ok(b.into())
+    }
+
+    fn visit_i64<E>(self, i: i64) -> std::result::Result<Expression, E> {
+        Ok(Expression::Integer(i))
+    }
+
+    fn visit_u64<E>(self, u: u64) -> std::result::Result<Expression, E> {
+        Ok(BigInt::from(u).into())
+    }
+
+    fn visit_i128<E>(self, i: i128) -> std::result::Result<Expression, E> {
+        Ok(BigInt::from(i).into())
+    }
+
+    fn visit_u128<E>(self, u: u128) -> std::result::Result<Expression, E> {
+        Ok(BigInt::from(u).into())
+    }
+
+    fn visit_f64<E>(self, f: f64) -> std::result::Result<Expression, E> {
+        Ok(Expression::Float(f))
+    }
+
+    fn visit_str<E>(self, s: &str) -> std::result::Result<Expression, E> {
+        Ok(Expression::String(s.to_string()))
+    }
+
+    fn visit_string<E>(self, s: String) -> std::result::Result<Expression, E> {
+        Ok(Expression::String(s))
+    }
+
+    fn visit_unit<E>(self) -> std::result::Result<Expression, E> {
+        Ok(Expression::Nil)
+    }
+
+    fn visit_none<E>(self) -> std::result::Result<Expression, E> {
+        Ok(Expression::Nil)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Expression, D::Error> {
+        Expression::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Expression, A::Error> {
+        let mut list = vec![];
+
+        while let Some(item) = seq.next_element()? {
+            list.push(item);
+        }
+
+        Ok(Expression::List(list.into()))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<Expression, A::Error> {
+        let mut table = Table::new();
+
+        while let Some(key) = map.next_key::<Expression>()? {
+            if table.is_empty() && matches!(&key, Expression::String(s) if s == JSON_NUMBER) {
+                return json_number(&map.next_value::<String>()?).map_err(de::Error::custom);
+            }
+
+            let value = map.next_value()?;
+            table.insert(key.as_key().map_err(de::Error::custom)?, value);
+        }
+
+        Ok(Expression::Table(table))
+    }
+}
+
+/// The key serde_json hands a number over under, as a map of one entry to
+/// its digits, when it doesn't fit in a `u64` or `i64`.
+const JSON_NUMBER: &str = "$serde_json::private::Number";
+
+/// A JSON number too big for a machine integer, exactly when it is an
+/// integer and as a float otherwise.
+fn json_number(number: &str) -> Result<Expression, Error> {
+    if let Ok(n) = number.parse::<BigInt>() {
+        return Ok(n.into());
+    }
+
+    number
+        .parse::<f64>()
+        .map(Expression::Float)
+        .map_err(|_| de::Error::custom(format!("Not a number: {number}")))
+}
+
+/// Serializes into an `Expression`.
+struct ExpressionSerializer;
+
+type Serialized = std::result::Result<Expression, Error>;
+
+impl Serializer for ExpressionSerializer {
+    type Ok = Expression;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeTable;
+    type SerializeStruct = SerializeTable;
+    type SerializeStructVariant = SerializeTable;
+
+    fn serialize_bool(self, v: bool) -> Serialized {
+        Ok(v.into())
+    }
+
+    fn serialize_i8(self, v: i8) -> Serialized {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Serialized {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Serialized {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Serialized {
+        Ok(Expression::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Serialized {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Serialized {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Serialized {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i128(self, v: i128) -> Serialized {
+        Ok(BigInt::from(v).into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Serialized {
+        Ok(BigInt::from(v).into())
+    }
+
+    fn serialize_u128(self, v: u128) -> Serialized {
+        Ok(BigInt::from(v).into())
+    }
+
+    fn serialize_f32(self, v: f32) -> Serialized {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Serialized {
+        Ok(Expression::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Serialized {
+        Ok(Expression::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Serialized {
+        Ok(Expression::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Serialized {
+        Ok(Expression::List(
+            v.iter().map(|b| Expression::Integer((*b).into())).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Serialized {
+        Ok(Expression::Nil)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Serialized {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Serialized {
+        Ok(Expression::Nil)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Serialized {
+        Ok(Expression::Nil)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Serialized {
+        Ok(Expression::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Serialized {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Serialized {
+        Ok(tagged(Some(variant), value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> std::result::Result<SerializeList, Error> {
+        Ok(SerializeList {
+            list: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> std::result::Result<SerializeList, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> std::result::Result<SerializeList, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> std::result::Result<SerializeList, Error> {
+        Ok(SerializeList {
+            list: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> std::result::Result<SerializeTable, Error> {
+        Ok(SerializeTable {
+            table: Table::new(),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> std::result::Result<SerializeTable, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> std::result::Result<SerializeTable, Error> {
+        Ok(SerializeTable {
+            table: Table::new(),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+/// Wraps the data of an enum variant in a table keyed by its name.
+fn tagged(variant: Option<&'static str>, value: Expression) -> Expression {
+    match variant {
+        Some(variant) => Expression::Table(Table::from([(Key::from(variant), value)])),
+        None => value,
+    }
+}
+
+struct SerializeList {
+    list: Vec<Expression>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> std::result::Result<(), Error> {
+        self.list.push(value.serialize(ExpressionSerializer)?);
+
+        Ok(())
+    }
+
+    fn finish(self) -> Serialized {
+        Ok(tagged(self.variant, Expression::List(self.list.into())))
+    }
+}
+
+impl SerializeSeq for SerializeList {
+    type Ok = Expression;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> std::result::Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Serialized {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Expression;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> std::result::Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Serialized {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Expression;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> std::result::Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Serialized {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Expression;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> std::result::Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Serialized {
+        self.finish()
+    }
+}
+
+struct SerializeTable {
+    table: Table,
+    key: Option<Key>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeTable {
+    fn insert<T: Serialize + ?Sized>(
+        &mut self,
+        key: Key,
+        value: &T,
+    ) -> std::result::Result<(), Error> {
+        self.table
+            .insert(key, value.serialize(ExpressionSerializer)?);
+
+        Ok(())
+    }
+
+    fn finish(self) -> Serialized {
+        Ok(tagged(self.variant, Expression::Table(self.table)))
+    }
+}
+
+impl SerializeMap for SerializeTable {
+    type Ok = Expression;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> std::result::Result<(), Error> {
+        let key = key.serialize(ExpressionSerializer)?;
+
+        self.key = Some(
+            key.as_key()
+                .map_err(|_| ser::Error::custom(format!("Can't use {key} as a table key")))?,
+        );
+
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> std::result::Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("Table value without a key"))?;
+
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Serialized {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeTable {
+    type Ok = Expression;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> std::result::Result<(), Error> {
+        self.insert(Key::from(key), value)
+    }
+
+    fn end(self) -> Serialized {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeTable {
+    type Ok = Expression;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> std::result::Result<(), Error> {
+        self.insert(Key::from(key), value)
+    }
+
+    fn end(self) -> Serialized {
+        self.finish()
+    }
+}
+
+impl<'de> Deserializer<'de> for &'de Expression {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Error> {
+        match self {
+            Expression::Bool(b) => visitor.visit_bool(*b),
+            Expression::Integer(i) => visitor.visit_i64(*i),
+            Expression::BigInt(n) => deserialize_bigint(n, visitor),
+            Expression::Rational(r) => visitor.visit_f64(r.to_f64().unwrap_or(f64::NAN)),
+            Expression::Float(f) => visitor.visit_f64(*f),
+            Expression::Char(c) => visitor.visit_char(*c),
+            Expression::String(s) | Expression::Symbol(s) | Expression::Keyword(s) => {
+                visitor.visit_borrowed_str(s)
+            }
+            Expression::List(list) => visitor.visit_seq(SeqDeserializer { items: list.iter() }),
+            Expression::Table(table) => visitor.visit_map(MapDeserializer {
+                entries: table.iter(),
+                value: None,
+            }),
+            Expression::Nil => visitor.visit_unit(),
+            _ => Err(de::Error::custom(format!(
+                "Can't deserialize a {}",
+                self.as_type_string()
+            ))),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Error> {
+        visitor.visit_bool(bool::from_expression(self).map_err(de::Error::custom)?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Error> {
+        match self {
+            Expression::Nil => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Error> {
+        match self {
+            Expression::String(variant)
+            | Expression::Symbol(variant)
+            | Expression::Keyword(variant) => visitor.visit_enum(EnumDeserializer {
+                variant,
+                value: None,
+            }),
+            Expression::Table(table) if table.len() == 1 => match table.iter().next().unwrap() {
+                (Key::String(variant) | Key::Symbol(variant) | Key::Keyword(variant), value) => {
+                    visitor.visit_enum(EnumDeserializer {
+                        variant,
+                        value: Some(value),
+                    })
+                }
+                (key, _) => Err(de::Error::custom(format!(
+                    "Expected an enum variant, got {key}"
+                ))),
+            },
+            _ => Err(de::Error::custom(format!(
+                "Expected an enum variant, got {self}"
+            ))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct SeqDeserializer<'de> {
+    items: std::slice::Iter<'de, Expression>,
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> std::result::Result<Option<T::Value>, Error> {
+        self.items
+            .next()
+            .map(|item| seed.deserialize(item))
+            .transpose()
+    }
+}
+
+struct MapDeserializer<'de> {
+    entries: indexmap::map::Iter<'de, Key, Expression>,
+    value: Option<&'de Expression>,
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> std::result::Result<Option<K::Value>, Error> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> std::result::Result<V::Value, Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("Table value without a key"))?;
+
+        seed.deserialize(value)
+    }
+}
+
+impl<'de> Deserializer<'de> for &'de Key {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, Error> {
+        match self {
+            Key::Bool(b) => visitor.visit_bool(*b),
+            Key::Integer(i) => visitor.visit_i64(*i),
+            Key::BigInt(n) => deserialize_bigint(n, visitor),
+            Key::Rational(r) => visitor.visit_f64(r.to_f64().unwrap_or(f64::NAN)),
+            Key::Char(c) => visitor.visit_char(*c),
+            Key::String(s) | Key::Symbol(s) | Key::Keyword(s) => visitor.visit_borrowed_str(s),
+            Key::List(keys) => visitor.visit_seq(de::value::SeqDeserializer::new(keys.iter())),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for &'de Key {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct EnumDeserializer<'de> {
+    variant: &'de str,
+    value: Option<&'de Expression>,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> std::result::Result<(V::Value, Self), Error> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for EnumDeserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> std::result::Result<(), Error> {
+        match self.value {
+            None | Some(Expression::Nil) => Ok(()),
+            Some(value) => Err(de::Error::custom(format!(
+                "Unexpected data for unit variant `{}`: {value}",
+                self.variant
+            ))),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> std::result::Result<T::Value, Error> {
+        seed.deserialize(self.value.unwrap_or(&Expression::Nil))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Error> {
+        self.value
+            .unwrap_or(&Expression::Nil)
+            .deserialize_any(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Error> {
+        self.value
+            .unwrap_or(&Expression::Nil)
+            .deserialize_any(visitor)
+    }
+}
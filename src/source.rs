@@ -0,0 +1,117 @@
+use color_eyre::{Report, Section};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// A piece of program text.
+#[derive(Debug)]
+pub struct Source {
+    pub name: String,
+    pub text: String,
+}
+
+/// Where a list was read from.
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub source: Rc<Source>,
+    pub span: Span,
+}
+
+impl Location {
+    pub fn position(&self) -> String {
+        self.source.position(self.span)
+    }
+
+    pub fn excerpt(&self) -> String {
+        self.source.excerpt(self.span)
+    }
+}
+
+impl Source {
+    pub fn new(name: &str, text: &str) -> Source {
+        Source {
+            name: name.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    /// One based line and column of the character at `offset`.
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let before = &self.text[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+        (line, before[line_start..].chars().count() + 1)
+    }
+
+    pub fn position(&self, span: Span) -> String {
+        let (line, column) = self.line_column(span.offset);
+
+        format!("{}:{line}:{column}", self.name)
+    }
+
+    /// `name:line:column` followed by the offending line, underlined.
+    pub fn excerpt(&self, span: Span) -> String {
+        let (line, column) = self.line_column(span.offset);
+
+        let line_start = self.text[..span.offset]
+            .rfind('\n')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let line_end = self.text[span.offset..]
+            .find('\n')
+            .map(|i| span.offset + i)
+            .unwrap_or(self.text.len());
+
+        let underlined = self.text[span.offset..(span.offset + span.length).min(line_end)]
+            .chars()
+            .count()
+            .max(1);
+
+        let gutter = " ".repeat(line.to_string().len());
+
+        format!(
+            "{gutter}--> {}:{line}:{column}\n{gutter} |\n{line} | {}\n{gutter} | {}{}",
+            self.name,
+            &self.text[line_start..line_end],
+            " ".repeat(column - 1),
+            "^".repeat(underlined)
+        )
+    }
+}
+
+/// An error tied to the place in the program that produced it. Displays
+/// exactly like the error it wraps.
+#[derive(Debug)]
+pub struct Located {
+    pub error: Report,
+    /// Excerpt of the innermost form with a known source.
+    pub location: Option<String>,
+    /// The call stack when the error was raised, innermost call first.
+    pub trace: Vec<String>,
+}
+
+impl std::fmt::Display for Located {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for Located {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+pub fn located(error: Report, location: String) -> Report {
+    Report::new(Located {
+        error,
+        location: Some(location.clone()),
+        trace: vec![],
+    })
+    .section(location)
+}
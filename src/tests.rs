@@ -2,8 +2,12 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use crate::builtin::std_lib;
-use crate::expression::Expression;
+use crate::convert::{FromExpression, IntoExpression};
+use crate::env::CapturedEnv;
+use crate::expression::{Arity, Expression, Key};
 use crate::run;
+use crate::source::Located;
+use crate::Interpreter;
 
 #[test]
 fn add_two_integers() {
@@ -31,11 +35,14 @@ fn map_numbers_add() {
 
     assert_eq!(
         result,
-        Expression::List(vec![
-            Expression::Integer(2),
-            Expression::Integer(3),
-            Expression::Integer(4)
-        ])
+        Expression::List(
+            vec![
+                Expression::Integer(2),
+                Expression::Integer(3),
+                Expression::Integer(4)
+            ]
+            .into()
+        )
     );
 }
 
@@ -56,14 +63,14 @@ fn filter() {
 
     assert_eq!(
         result,
-        Expression::List(vec![Expression::Integer(1), Expression::Integer(2)])
+        Expression::List(vec![Expression::Integer(1), Expression::Integer(2)].into())
     );
 
     let result = run(&mut std, "(filter (function '(x) '(> x 3)) '(1 2 3 4 5))").unwrap();
 
     assert_eq!(
         result,
-        Expression::List(vec![Expression::Integer(4), Expression::Integer(5)])
+        Expression::List(vec![Expression::Integer(4), Expression::Integer(5)].into())
     );
 }
 
@@ -89,11 +96,16 @@ fn create_add_xy_function() {
                 Expression::Symbol("x".into()),
                 Expression::Symbol("y".into())
             ],
-            body: Box::new(Expression::List(vec![
-                Expression::Symbol("+".into()),
-                Expression::Symbol("x".into()),
-                Expression::Symbol("y".into())
-            ]))
+            body: Box::new(Expression::List(
+                vec![
+                    Expression::Symbol("+".into()),
+                    Expression::Symbol("x".into()),
+                    Expression::Symbol("y".into())
+                ]
+                .into()
+            )),
+            env: CapturedEnv(std.clone()),
+            name: None,
         }
     );
 }
@@ -108,6 +120,7 @@ fn add_xy_function() {
 }
 
 #[test]
+#[allow(clippy::needless_range_loop)]
 fn fibonacci() {
     let mut std = Rc::new(RefCell::new(std_lib()));
 
@@ -189,12 +202,17 @@ fn partial_application_left() {
                 Expression::Symbol("y".into()),
                 Expression::Symbol("z".into())
             ],
-            body: Box::new(Expression::List(vec![
-                Expression::Symbol("+".into()),
-                Expression::Symbol("x".into()),
-                Expression::Symbol("y".into()),
-                Expression::Symbol("z".into())
-            ]))
+            body: Box::new(Expression::List(
+                vec![
+                    Expression::Symbol("+".into()),
+                    Expression::Symbol("x".into()),
+                    Expression::Symbol("y".into()),
+                    Expression::Symbol("z".into())
+                ]
+                .into()
+            )),
+            env: CapturedEnv(std.clone()),
+            name: None,
         }
     );
 
@@ -207,10 +225,17 @@ fn partial_application_left() {
                 Expression::Symbol("y".into()),
                 Expression::Symbol("z".into())
             ],
-            body: Box::new(Expression::List(vec![
-                run(&mut std, "add-xyz").unwrap(),
-                Expression::Integer(1),
-            ]))
+            body: Box::new(Expression::List(
+                vec![
+                    run(&mut std, "add-xyz").unwrap(),
+                    Expression::Integer(1),
+                    Expression::Symbol("y".into()),
+                    Expression::Symbol("z".into()),
+                ]
+                .into()
+            )),
+            env: CapturedEnv(std.clone()),
+            name: None,
         }
     );
 
@@ -220,89 +245,209 @@ fn partial_application_left() {
         result,
         Expression::Function {
             arguments: vec![Expression::Symbol("z".into())],
-            body: Box::new(Expression::List(vec![
-                run(&mut std, "add-xyz").unwrap(),
-                Expression::Integer(1),
-                Expression::Integer(2),
-            ]))
+            body: Box::new(Expression::List(
+                vec![
+                    run(&mut std, "add-xyz").unwrap(),
+                    Expression::Integer(1),
+                    Expression::Integer(2),
+                    Expression::Symbol("z".into()),
+                ]
+                .into()
+            )),
+            env: CapturedEnv(std.clone()),
+            name: None,
         }
     );
 
     let result = run(&mut std, "(add-xyz 1 2 3)").unwrap();
 
     assert_eq!(result, Expression::Integer(6));
+
+    let result = run(&mut std, "((add-xyz 1 2) 3)").unwrap();
+
+    assert_eq!(result, Expression::Integer(6));
+
+    let result = run(&mut std, "(((add-xyz 1) 2) 3)").unwrap();
+
+    assert_eq!(result, Expression::Integer(6));
+
+    let error = run(&mut std, "(add-xyz 1 2 3 4)").unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "function `add-xyz` expects 3 arguments, got 4"
+    );
+}
+
+#[test]
+fn partial_application_evaluates_arguments_once() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(
+        &mut std,
+        "(define 'add (function '(x y) '(+ x y)))
+(define 'pair (function '(x y) '(list x y)))
+(define 'n 1)
+(define 'add-n (add n))
+(define 'n 100)",
+    )
+    .unwrap();
+
+    let result = run(&mut std, "(add-n 2)").unwrap();
+
+    assert_eq!(result, Expression::Integer(3));
+
+    let result = run(&mut std, "((pair '(1 2)) 'y)").unwrap();
+
+    assert_eq!(
+        result,
+        Expression::List(
+            vec![
+                Expression::List(vec![Expression::Integer(1), Expression::Integer(2)].into()),
+                Expression::Symbol("y".into()),
+            ]
+            .into()
+        )
+    );
+
+    let result = run(&mut std, "(let 'y 5 ((add y) 1))").unwrap();
+
+    assert_eq!(result, Expression::Integer(6));
+}
+
+#[test]
+fn closure_captures_defining_env() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(
+        &mut std,
+        "(define 'make-adder (function '(n) '(function '(x) '(+ x n))))",
+    )
+    .unwrap();
+    run(&mut std, "(define 'add5 (make-adder 5))").unwrap();
+
+    let result = run(&mut std, "(add5 10)").unwrap();
+
+    assert_eq!(result, Expression::Integer(15));
+
+    let result = run(&mut std, "(let 'n 100 (add5 1))").unwrap();
+
+    assert_eq!(result, Expression::Integer(6));
+}
+
+#[test]
+fn partial_application_right() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(
+        &mut std,
+        "(define 'add-xyz (
+            function '(x y z) '(+ x y z)
+        ))",
+    )
+    .unwrap();
+
+    let result = run(&mut std, "(add-xyz _ _ 3)").unwrap();
+
+    assert_eq!(
+        result,
+        Expression::Function {
+            arguments: vec![
+                Expression::Symbol("x".into()),
+                Expression::Symbol("y".into())
+            ],
+            body: Box::new(Expression::List(
+                vec![
+                    run(&mut std, "add-xyz").unwrap(),
+                    Expression::Symbol("x".into()),
+                    Expression::Symbol("y".into()),
+                    Expression::Integer(3)
+                ]
+                .into()
+            )),
+            env: CapturedEnv(std.clone()),
+            name: None,
+        }
+    );
+
+    let result = run(&mut std, "((add-xyz _ 2 3) 1)").unwrap();
+
+    assert_eq!(result, Expression::Integer(6));
+
+    let result = run(&mut std, "((add-xyz _ 2) 1 3)").unwrap();
+
+    assert_eq!(result, Expression::Integer(6));
 }
 
-// #[test]
-// fn partial_application_right() {
-//     let mut std = Rc::new(RefCell::new(std_lib()));
-
-//     let result = run(
-//         &mut std,
-//         "(define add-xyz (
-//             function '(x y z) '(+ x y z)
-//         ))",
-//     )
-//     .unwrap();
-
-//     assert_eq!(result, Expression::Nil);
-
-//     let result = run(&mut std, "add-xyz").unwrap();
-
-//     assert_eq!(
-//         result,
-//         Expression::Function {
-//             arguments: vec![
-//                 Expression::Symbol("x".into()),
-//                 Expression::Symbol("y".into()),
-//                 Expression::Symbol("z".into())
-//             ],
-//             body: Box::new(Expression::List(vec![
-//                 Expression::Symbol("+".into()),
-//                 Expression::Symbol("x".into()),
-//                 Expression::Symbol("y".into()),
-//                 Expression::Symbol("z".into())
-//             ]))
-//         }
-//     );
-
-//     let result = run(&mut std, "(add-xyz _ _ 3)").unwrap();
-
-//     assert_eq!(
-//         result,
-//         Expression::Function {
-//             arguments: vec![
-//                 Expression::Symbol("x".into()),
-//                 Expression::Symbol("y".into())
-//             ],
-//             body: Box::new(Expression::List(vec![
-//                 Expression::Symbol("+".into()),
-//                 Expression::Symbol("x".into()),
-//                 Expression::Symbol("y".into()),
-//                 Expression::Integer(3)
-//             ]))
-//         }
-//     );
-
-//     let result = run(&mut std, "(add-xyz _ 2 3)").unwrap();
-
-//     assert_eq!(
-//         result,
-//         Expression::Function {
-//             arguments: vec![Expression::Symbol("x".into())],
-//             body: Box::new(Expression::List(vec![
-//                 Expression::Symbol("+".into()),
-//                 Expression::Symbol("x".into()),
-//                 Expression::Integer(2),
-//                 Expression::Integer(3)
-//             ]))
-//         }
-//     );
-
-//     let result = run(&mut std, "(add-xyz 1 2 3)").unwrap();
-
-//     assert_eq!(result, Expression::Integer(6));
-// }
+#[test]
+fn partial_application_of_builtins() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, "(map (+ 1 _) '(1 2 3))").unwrap();
+
+    assert_eq!(
+        result,
+        Expression::List(
+            vec![
+                Expression::Integer(2),
+                Expression::Integer(3),
+                Expression::Integer(4)
+            ]
+            .into()
+        )
+    );
+
+    let result = run(&mut std, "(map (- _ 1) '(1 2 3))").unwrap();
+
+    assert_eq!(
+        result,
+        Expression::List(
+            vec![
+                Expression::Integer(0),
+                Expression::Integer(1),
+                Expression::Integer(2)
+            ]
+            .into()
+        )
+    );
+
+    let result = run(&mut std, "(map (nth _ '(a b c)) '(2 0))").unwrap();
+
+    assert_eq!(
+        result,
+        Expression::List(
+            vec![
+                Expression::Symbol("c".into()),
+                Expression::Symbol("a".into())
+            ]
+            .into()
+        )
+    );
+
+    let result = run(&mut std, "(filter (< 1 _) '(0 1 2 3))").unwrap();
+
+    assert_eq!(
+        result,
+        Expression::List(vec![Expression::Integer(2), Expression::Integer(3)].into())
+    );
+
+    let result = run(&mut std, "(let 'y 10 (map (+ y _) '(1 2)))").unwrap();
+
+    assert_eq!(
+        result,
+        Expression::List(vec![Expression::Integer(11), Expression::Integer(12)].into())
+    );
+
+    let result = run(&mut std, "((fold +) 0 '(1 2 3))").unwrap();
+
+    assert_eq!(result, Expression::Integer(6));
+
+    let result = run(&mut std, "(- 5)").unwrap();
+
+    assert_eq!(result, Expression::Integer(-5));
+
+    assert_eq!(run(&mut std, "'_").unwrap(), Expression::Symbol("_".into()));
+}
 
 #[test]
 fn and_then() {
@@ -328,13 +473,16 @@ fn range() {
 
     assert_eq!(
         result,
-        Expression::List(vec![
-            Expression::Integer(1),
-            Expression::Integer(2),
-            Expression::Integer(3),
-            Expression::Integer(4),
-            Expression::Integer(5),
-        ])
+        Expression::List(
+            vec![
+                Expression::Integer(1),
+                Expression::Integer(2),
+                Expression::Integer(3),
+                Expression::Integer(4),
+                Expression::Integer(5),
+            ]
+            .into()
+        )
     );
 }
 
@@ -363,12 +511,15 @@ fn append() {
 
     assert_eq!(
         result,
-        Expression::List(vec![
-            Expression::Integer(1),
-            Expression::Integer(2),
-            Expression::Integer(3),
-            Expression::Integer(4),
-        ])
+        Expression::List(
+            vec![
+                Expression::Integer(1),
+                Expression::Integer(2),
+                Expression::Integer(3),
+                Expression::Integer(4),
+            ]
+            .into()
+        )
     );
 }
 
@@ -380,12 +531,15 @@ fn prepend() {
 
     assert_eq!(
         result,
-        Expression::List(vec![
-            Expression::Integer(1),
-            Expression::Integer(2),
-            Expression::Integer(3),
-            Expression::Integer(4),
-        ])
+        Expression::List(
+            vec![
+                Expression::Integer(1),
+                Expression::Integer(2),
+                Expression::Integer(3),
+                Expression::Integer(4),
+            ]
+            .into()
+        )
     );
 }
 
@@ -426,25 +580,2317 @@ fn slice() {
 
     assert_eq!(
         result,
-        Expression::List(vec![
-            Expression::Integer(1),
-            Expression::Integer(2),
-            Expression::Integer(3)
-        ])
+        Expression::List(
+            vec![
+                Expression::Integer(1),
+                Expression::Integer(2),
+                Expression::Integer(3)
+            ]
+            .into()
+        )
     );
 
     let result = run(&mut std, "(slice 0 2 '(1 2 3))").unwrap();
 
     assert_eq!(
         result,
-        Expression::List(vec![Expression::Integer(1), Expression::Integer(2),])
+        Expression::List(vec![Expression::Integer(1), Expression::Integer(2),].into())
     );
 
     let result = run(&mut std, "(slice 0 1 '(1 2 3))").unwrap();
 
-    assert_eq!(result, Expression::List(vec![Expression::Integer(1)]));
+    assert_eq!(
+        result,
+        Expression::List(vec![Expression::Integer(1)].into())
+    );
 
     let result = run(&mut std, "(slice 0 0 '(1 2 3))").unwrap();
 
-    assert_eq!(result, Expression::List(vec![]));
+    assert_eq!(result, Expression::List(vec![].into()));
+}
+
+#[test]
+fn tail_calls_do_not_grow_the_stack() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(
+        &mut std,
+        "(define 'count-up (function '(n acc)
+            '(if (= n 0) acc (count-up (- n 1) (+ acc 1)))))",
+    )
+    .unwrap();
+
+    let result = run(&mut std, "(count-up 20000 0)").unwrap();
+
+    assert_eq!(result, Expression::Integer(20000));
+
+    run(
+        &mut std,
+        "(define 'sum-to (function '(n acc)
+            '(let 'next (- n 1)
+                (and-then
+                    (if (< n 1) acc (sum-to next (+ acc n)))))))",
+    )
+    .unwrap();
+
+    let result = run(&mut std, "(sum-to 20000 0)").unwrap();
+
+    assert_eq!(result, Expression::Integer(200010000));
+}
+
+#[test]
+fn quasiquote() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, "(let 'x 2 `(1 ,x ,@(list 3 4) ,(+ x 3)))").unwrap();
+
+    assert_eq!(
+        result,
+        Expression::List(
+            vec![
+                Expression::Integer(1),
+                Expression::Integer(2),
+                Expression::Integer(3),
+                Expression::Integer(4),
+                Expression::Integer(5),
+            ]
+            .into()
+        )
+    );
+
+    let result = run(&mut std, "`(a `(b ,(c ,(+ 1 2))))").unwrap();
+
+    assert_eq!(result, run(&mut std, "'(a `(b ,(c 3)))").unwrap());
+}
+
+#[test]
+fn macros() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(
+        &mut std,
+        "(and-then
+            (defmacro 'unless '(condition & body)
+                '`(if ,condition nil (and-then ,@body)))
+            (defmacro 'cond '((test expression) & more)
+                '`(if ,test ,expression ,(if (= (length more) 0) nil `(cond ,@more))))
+            (defmacro '-> '(x (f & args) & more)
+                '(if (= (length more) 0)
+                    `(,f ,x ,@args)
+                    `(-> (,f ,x ,@args) ,@more))))",
+    )
+    .unwrap();
+
+    let result = run(&mut std, "(unless (= 1 2) (define 'y 5) (+ y 1))").unwrap();
+
+    assert_eq!(result, Expression::Integer(6));
+
+    let result = run(&mut std, "(unless (= 1 1) (undefined-function))").unwrap();
+
+    assert_eq!(result, Expression::Nil);
+
+    run(
+        &mut std,
+        "(define 'sign (function '(x) '(cond ((< x 0) \"negative\") ((= x 0) \"zero\") (t \"positive\"))))",
+    )
+    .unwrap();
+
+    assert_eq!(
+        run(&mut std, "(sign -5)").unwrap(),
+        Expression::String("negative".into())
+    );
+    assert_eq!(
+        run(&mut std, "(sign 0)").unwrap(),
+        Expression::String("zero".into())
+    );
+    assert_eq!(
+        run(&mut std, "(sign 7)").unwrap(),
+        Expression::String("positive".into())
+    );
+
+    let result = run(&mut std, "(-> 5 (+ 1) (* 2) (- 2))").unwrap();
+
+    assert_eq!(result, Expression::Integer(10));
+
+    let result = run(&mut std, "(macroexpand '(-> 5 (+ 1) (* 2)))").unwrap();
+
+    assert_eq!(result, run(&mut std, "'(* (+ 5 1) 2)").unwrap());
+}
+
+#[test]
+fn macros_expand_before_evaluation() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(
+        &mut std,
+        "(define 'expansions 0)
+(defmacro 'twice '(x)
+    '(and-then
+        (define 'expansions (+ expansions 1))
+        `(* 2 ,x)))
+(defmacro 'broken '() '(throw \"expansion failed\"))
+(define 'f (function '(n) '(twice n)))",
+    )
+    .unwrap();
+
+    let result = run(&mut std, "(list (f 1) (f 2) (f 3))").unwrap();
+
+    assert_eq!(
+        result,
+        Expression::List(
+            vec![
+                Expression::Integer(2),
+                Expression::Integer(4),
+                Expression::Integer(6)
+            ]
+            .into()
+        )
+    );
+
+    run(&mut std, "(for 'i '(1 2 3) '(twice i))").unwrap();
+
+    assert_eq!(run(&mut std, "expansions").unwrap(), Expression::Integer(2));
+
+    assert_eq!(
+        run(&mut std, "(macroexpand '(twice (twice 1)))").unwrap(),
+        run(&mut std, "'(* 2 (twice 1))").unwrap()
+    );
+
+    let error = run(&mut std, "(and-then (define 'ran true) (broken))").unwrap_err();
+
+    assert_eq!(error.to_string(), "expansion failed");
+    assert_eq!(run(&mut std, "ran").unwrap(), Expression::Nil);
+}
+
+#[test]
+fn gensym_is_unique() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, "(= (gensym) (gensym))").unwrap();
+
+    assert_eq!(result, Expression::Bool(false));
+}
+
+#[test]
+fn comments() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(
+        &mut std,
+        "; a line comment before the first form
+        (define 'x 5) ; trailing comment
+        #| a block comment
+           #| which nests |#
+           (define 'x 100)
+        |#
+        (+ x ; inside a list
+           #;(this datum is skipped) 7 #; 1000)
+        ; and one at the very end",
+    )
+    .unwrap();
+
+    assert_eq!(result, Expression::Integer(12));
+
+    let result = run(&mut std, "; only a comment").unwrap();
+
+    assert_eq!(result, Expression::Nil);
+
+    assert!(run(&mut std, "#| never closed").is_err());
+}
+
+#[test]
+fn string_escapes() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, r#""say \"hi\"\n\tback\\slash \u{1F600}""#).unwrap();
+
+    assert_eq!(
+        result,
+        Expression::String("say \"hi\"\n\tback\\slash 😀".into())
+    );
+
+    let result = run(&mut std, r#""""#).unwrap();
+
+    assert_eq!(result, Expression::String("".into()));
+
+    assert!(run(&mut std, r#""bad \q escape""#).is_err());
+    assert!(run(&mut std, r#""never closed"#).is_err());
+}
+
+#[test]
+fn raw_strings() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, r##"#r"C:\path\no "escapes" here"#"##).unwrap();
+
+    assert_eq!(
+        result,
+        Expression::String(r#"C:\path\no "escapes" here"#.into())
+    );
+
+    let result = run(&mut std, r###"#r#"ends with "# inside"##"###).unwrap();
+
+    assert_eq!(
+        result,
+        Expression::String(r##"ends with "# inside"##.into())
+    );
+}
+
+#[test]
+fn strings_round_trip() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let original = Expression::String("quote \" slash \\ newline \n tab \t bell \u{7}".into());
+
+    let result = run(&mut std, &original.as_debug_string()).unwrap();
+
+    assert_eq!(result, original);
+}
+
+#[test]
+fn errors_point_at_their_source() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let error = run(
+        &mut std,
+        "(define 'f (function '(x)
+    '(+ x \"a\")))
+(f 1)",
+    )
+    .unwrap_err();
+    let located = error.downcast_ref::<Located>().unwrap();
+
+    assert_eq!(located.error.to_string(), "Can't add 1 and \"a\"");
+    assert_eq!(
+        located.location.as_deref().unwrap(),
+        " --> <input>:2:6
+  |
+2 |     '(+ x \"a\")))
+  |      ^^^^^^^^^"
+    );
+
+    let error = run(&mut std, "(+ 1 2)\n  (list 1 (+ 2 \"x\"))").unwrap_err();
+    let located = error.downcast_ref::<Located>().unwrap();
+
+    assert!(located
+        .location
+        .as_deref()
+        .unwrap()
+        .starts_with(" --> <input>:2:11\n"));
+
+    let error = run(
+        &mut std,
+        "(try (nth 5 (list 1)) (catch e nil))\n(nth 5 (list 1))",
+    )
+    .unwrap_err();
+    let located = error.downcast_ref::<Located>().unwrap();
+
+    assert!(located
+        .location
+        .as_deref()
+        .unwrap()
+        .starts_with(" --> <input>:2:1\n"));
+
+    let error = run(&mut std, "(list 1))").unwrap_err();
+    let located = error.downcast_ref::<Located>().unwrap();
+
+    assert_eq!(located.error.to_string(), "Parse error: Unexpected ')'");
+    assert!(located
+        .location
+        .as_deref()
+        .unwrap()
+        .starts_with(" --> <input>:1:9\n"));
+}
+
+#[test]
+fn parse_errors_at_non_ascii_characters_are_located() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let error = run(&mut std, "€").unwrap_err();
+    let located = error.downcast_ref::<Located>().unwrap();
+
+    assert_eq!(located.error.to_string(), "Parse error: Unexpected '€'");
+    assert_eq!(
+        located.location.as_deref().unwrap(),
+        " --> <input>:1:1\n  |\n1 | €\n  | ^"
+    );
+
+    let error = run(&mut std, "(list 1 😀)").unwrap_err();
+    let located = error.downcast_ref::<Located>().unwrap();
+
+    assert_eq!(located.error.to_string(), "Parse error: Unexpected '😀'");
+    assert!(located
+        .location
+        .as_deref()
+        .unwrap()
+        .starts_with(" --> <input>:1:9\n"));
+}
+
+#[test]
+fn errors_carry_a_traceback() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let error = run(
+        &mut std,
+        "(define 'add (function '(x y) '(+ x y \"2\")))
+(define 'go (function '(xs) '(fold add 0 xs)))
+(go '(1 2))",
+    )
+    .unwrap_err();
+    let located = error.downcast_ref::<Located>().unwrap();
+
+    assert_eq!(
+        located.trace,
+        vec![
+            "(+ x y \"2\") at <input>:1:32",
+            "(add 0 1)",
+            "(fold add 0 xs) at <input>:2:30",
+            "(go (1 2)) at <input>:3:1",
+        ]
+    );
+
+    run(
+        &mut std,
+        "(define 'count-down (function '(n)
+            '(if (= n 0) (+ n \"a\") (count-down (- n 1)))))",
+    )
+    .unwrap();
+
+    let error = run(&mut std, "(count-down 3)").unwrap_err();
+    let located = error.downcast_ref::<Located>().unwrap();
+
+    assert_eq!(
+        located.trace,
+        vec![
+            "(+ n \"a\") at <input>:2:26",
+            "(count-down 0) at <input>:2:36",
+        ]
+    );
+}
+
+#[test]
+fn malformed_try_clauses_are_reported() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let error = run(&mut std, "(try (/ 1 0) (catch))").unwrap_err();
+    let located = error.downcast_ref::<Located>().unwrap();
+
+    assert_eq!(error.to_string(), "catch expects a name for the error");
+    assert!(located
+        .location
+        .as_deref()
+        .unwrap()
+        .starts_with(" --> <input>:1:14\n"));
+
+    let error = run(&mut std, "(try (/ 1 0) (catch 1 2))").unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "catch names the error with a symbol, got 1"
+    );
+
+    let error = run(&mut std, "(try 1 (catch 1 2))").unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "catch names the error with a symbol, got 1"
+    );
+
+    let error = run(&mut std, "(try 1 (finally) (finally))").unwrap_err();
+
+    assert_eq!(error.to_string(), "try takes at most one finally clause");
+}
+
+#[test]
+fn try_catch_finally() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(try (+ 1 2) (catch e \"unreachable\"))").unwrap(),
+        Expression::Integer(3)
+    );
+
+    let error = run(&mut std, "(try (/ 1 0) (catch e e))")
+        .unwrap()
+        .as_table()
+        .unwrap();
+
+    assert_eq!(
+        error[&Key::from("message")],
+        Expression::String("Division by zero".into())
+    );
+    assert_eq!(
+        error[&Key::from("kind")],
+        Expression::String("arithmetic".into())
+    );
+    assert_eq!(
+        error[&Key::from("trace")],
+        Expression::List(
+            vec![
+                Expression::String("(/ 1 0) at <input>:1:6".into()),
+                Expression::String("(try (/ 1 0) (catch e e)) at <input>:1:1".into()),
+            ]
+            .into()
+        )
+    );
+
+    let error = run(&mut std, "(try (nth 0 5) (catch e e))")
+        .unwrap()
+        .as_table()
+        .unwrap();
+
+    assert_eq!(error[&Key::from("kind")], Expression::String("type".into()));
+
+    let error = run(&mut std, "(try (read \"/does/not/exist\") (catch e e))")
+        .unwrap()
+        .as_table()
+        .unwrap();
+
+    assert_eq!(error[&Key::from("kind")], Expression::String("io".into()));
+
+    let error = run(
+        &mut std,
+        "(try (map (function '(x) '(/ 1 x)) '(1 0)) (catch e e))",
+    )
+    .unwrap()
+    .as_table()
+    .unwrap();
+
+    assert_eq!(
+        error[&Key::from("kind")],
+        Expression::String("arithmetic".into())
+    );
+
+    let error = run(
+        &mut std,
+        "(try (zip-with (function '(x y) '(throw x)) '(1) '(2)) (catch e e))",
+    )
+    .unwrap()
+    .as_table()
+    .unwrap();
+
+    assert_eq!(error[&Key::from("value")], Expression::Integer(1));
+
+    let error = run(&mut std, "(try (throw '(1 2)) (catch e e))")
+        .unwrap()
+        .as_table()
+        .unwrap();
+
+    assert_eq!(
+        error[&Key::from("kind")],
+        Expression::String("thrown".into())
+    );
+    assert_eq!(
+        error[&Key::from("value")],
+        Expression::List(vec![Expression::Integer(1), Expression::Integer(2)].into())
+    );
+
+    assert_eq!(
+        run(
+            &mut std,
+            "(define 'cleaned nil)
+            (list
+                (try (throw \"oops\") (catch e (concat \"caught \" \"it\")) (finally (define 'cleaned t)))
+                cleaned)"
+        )
+        .unwrap(),
+        Expression::List(vec![Expression::String("caught it".into()), Expression::Bool(true)].into())
+    );
+
+    run(&mut std, "(define 'cleaned nil)").unwrap();
+    let error = run(
+        &mut std,
+        "(try (throw \"oops\") (finally (define 'cleaned t)))",
+    )
+    .unwrap_err();
+
+    assert_eq!(error.to_string(), "oops");
+    assert_eq!(run(&mut std, "cleaned").unwrap(), Expression::Bool(true));
+
+    let error = run(&mut std, "(try (throw 1) (catch e (throw 2)))").unwrap_err();
+
+    assert_eq!(error.to_string(), "2");
+}
+
+#[test]
+fn builtin_arity_is_checked() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let error = run(&mut std, "(nth 1 '(1 2) 3)").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "builtin `nth` expects 2 arguments, got 3"
+    );
+
+    let error = run(&mut std, "(if)").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "builtin `if` expects 2 to 3 arguments, got 0"
+    );
+
+    let error = run(&mut std, "(gensym \"a\" \"b\")").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "builtin `gensym` expects 0 to 1 arguments, got 2"
+    );
+
+    let error = run(&mut std, "(let 'x)").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "builtin `let` expects 3 arguments, got 1"
+    );
+
+    let error = run(&mut std, "(+ 1)").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "builtin `+` expects at least 2 arguments, got 1"
+    );
+
+    let error = run(&mut std, "(map (+ 1) '(1 2 3))").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "builtin `+` expects at least 2 arguments, got 1"
+    );
+
+    let error = run(&mut std, "(print 1 2)").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "builtin `print` expects 1 argument, got 2"
+    );
+
+    let error = run(&mut std, "(try (nth 1 2 3) (catch e e))")
+        .unwrap()
+        .as_table()
+        .unwrap();
+    assert_eq!(
+        error[&Key::from("kind")],
+        Expression::String("arity".into())
+    );
+
+    assert!(run(&mut std, "(nth 3 '(1 2 3))").is_err());
+    assert!(run(&mut std, "(filter (function '(x) '(+ x \"a\")) '(1 2))").is_err());
+    assert!(run(&mut std, "(export 'undefined-symbol)").is_err());
+
+    let error = run(&mut std, "()").unwrap_err();
+    assert_eq!(error.to_string(), "Can't call an empty list");
+
+    let error = run(&mut std, "(let* '((a)) a)").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "let* expects (name value) pairs, got (a)"
+    );
+}
+
+#[test]
+fn failing_arguments_of_equal_and_or_are_errors() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let error = run(&mut std, "(= 1 (/ 1 0))").unwrap_err();
+
+    assert_eq!(error.to_string(), "Division by zero");
+
+    let error = run(&mut std, "(and (/ 1 0))").unwrap_err();
+
+    assert_eq!(error.to_string(), "Division by zero");
+
+    let error = run(&mut std, "(or (/ 1 0))").unwrap_err();
+
+    assert_eq!(error.to_string(), "Division by zero");
+}
+
+#[test]
+fn try_catches_errors_in_arguments_of_builtins() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let arithmetic = Expression::String("arithmetic".into());
+
+    assert_eq!(
+        run(&mut std, r#"(try (= (/ 1 0) 1) (catch e (get e "kind")))"#).unwrap(),
+        arithmetic
+    );
+    assert_eq!(
+        run(&mut std, r#"(try (and (/ 1 0)) (catch e (get e "kind")))"#).unwrap(),
+        arithmetic
+    );
+    assert_eq!(
+        run(&mut std, r#"(try (or (/ 1 0) 1) (catch e (get e "kind")))"#).unwrap(),
+        arithmetic
+    );
+    assert_eq!(
+        run(
+            &mut std,
+            r#"(try (concat "a" (/ 1 0)) (catch e (get e "kind")))"#
+        )
+        .unwrap(),
+        arithmetic
+    );
+}
+
+#[test]
+fn interpreter() {
+    let mut interpreter = Interpreter::new();
+
+    interpreter
+        .eval_str("(define 'add (function '(x y) '(+ x y)))")
+        .unwrap();
+    interpreter.set("offset", Expression::Integer(10));
+
+    assert_eq!(
+        interpreter
+            .call("add", &[Expression::Integer(1), Expression::Integer(2)])
+            .unwrap(),
+        Expression::Integer(3)
+    );
+    assert_eq!(
+        interpreter.eval_str("(add offset 5)").unwrap(),
+        Expression::Integer(15)
+    );
+    assert_eq!(
+        interpreter
+            .call(
+                "length",
+                &[Expression::List(
+                    vec![
+                        Expression::Symbol("a".into()),
+                        Expression::Symbol("b".into())
+                    ]
+                    .into()
+                )]
+            )
+            .unwrap(),
+        Expression::Integer(2)
+    );
+
+    interpreter.eval_str("(define 'answer 42)").unwrap();
+
+    assert_eq!(interpreter.get("answer"), Some(Expression::Integer(42)));
+    assert!(interpreter.call("undefined", &[]).is_err());
+    assert!(interpreter.eval_file("/does/not/exist.arc").is_err());
+}
+
+#[test]
+fn native_functions() {
+    let mut interpreter = Interpreter::new();
+
+    let calls = Rc::new(std::cell::Cell::new(0));
+    let counter = calls.clone();
+
+    interpreter.register("tick", Arity::exactly(0), move |_, _| {
+        counter.set(counter.get() + 1);
+
+        Ok(Expression::Integer(counter.get()))
+    });
+    interpreter.register("add", Arity::exactly(2), |_, arguments| {
+        arguments[0].clone() + arguments[1].clone()
+    });
+    interpreter.register("twice", Arity::exactly(2), |interpreter, arguments| {
+        let once = interpreter.call_value(&arguments[0], &[arguments[1].clone()])?;
+
+        interpreter.call_value(&arguments[0], &[once])
+    });
+
+    interpreter.eval_str("(tick) (tick)").unwrap();
+
+    assert_eq!(calls.get(), 2);
+    assert_eq!(
+        interpreter.eval_str("(add (+ 1 2) 4)").unwrap(),
+        Expression::Integer(7)
+    );
+    assert_eq!(
+        interpreter.eval_str("(map (add 10) '(1 2))").unwrap(),
+        Expression::List(vec![Expression::Integer(11), Expression::Integer(12)].into())
+    );
+    assert_eq!(
+        interpreter
+            .eval_str("(twice (function '(x) '(* x 3)) 2)")
+            .unwrap(),
+        Expression::Integer(18)
+    );
+    assert_eq!(
+        interpreter.eval_str("(type add)").unwrap(),
+        Expression::String("builtin".into())
+    );
+    assert!(interpreter.eval_str("(add 1 2 3)").is_err());
+
+    let mut env = std_lib();
+    env.register("answer", Arity::exactly(0), |_, _| {
+        Ok(Expression::Integer(42))
+    });
+
+    assert_eq!(
+        Interpreter::with_env(env).eval_str("(answer)").unwrap(),
+        Expression::Integer(42)
+    );
+}
+
+#[test]
+fn conversions() {
+    let pairs = vec![(1u8, "one".to_string()), (2, "two".to_string())];
+    let expression = pairs.clone().into_expression();
+
+    assert_eq!(
+        expression,
+        Expression::List(
+            vec![
+                Expression::List(
+                    vec![Expression::Integer(1), Expression::String("one".into())].into()
+                ),
+                Expression::List(
+                    vec![Expression::Integer(2), Expression::String("two".into())].into()
+                ),
+            ]
+            .into()
+        )
+    );
+    assert_eq!(
+        Vec::<(u8, String)>::from_expression(&expression).unwrap(),
+        pairs
+    );
+
+    assert_eq!(
+        Option::<i64>::from_expression(&Expression::Nil).unwrap(),
+        None
+    );
+    assert_eq!(Some(2.5).into_expression(), Expression::Float(2.5));
+    assert_eq!(f64::from_expression(&Expression::Integer(2)).unwrap(), 2.0);
+    assert!(u8::from_expression(&Expression::Integer(300)).is_err());
+    assert!(String::from_expression(&Expression::Integer(1)).is_err());
+
+    let big = u64::MAX.into_expression();
+
+    assert!(matches!(big, Expression::BigInt(_)));
+    assert_eq!(u64::from_expression(&big).unwrap(), u64::MAX);
+    assert!(i64::from_expression(&big).is_err());
+    assert_eq!(
+        i128::from_expression(&i128::MIN.into_expression()).unwrap(),
+        i128::MIN
+    );
+
+    assert!(bool::from_expression(&Expression::Bool(true)).unwrap());
+    assert!(!bool::from_expression(&Expression::Nil).unwrap());
+    assert!(bool::from_expression(&Expression::Integer(0)).is_err());
+    assert!(bool::from_expression(&Expression::String("false".into())).is_err());
+
+    let scores = std::collections::HashMap::from([("ada".to_string(), 3)]);
+
+    assert_eq!(
+        std::collections::HashMap::<String, i32>::from_expression(
+            &scores.clone().into_expression()
+        )
+        .unwrap(),
+        scores
+    );
+}
+
+#[test]
+fn derived_conversions() {
+    #[derive(Debug, Clone, PartialEq, IntoExpression, FromExpression)]
+    struct Point(i64, i64);
+
+    #[derive(Debug, Clone, PartialEq, IntoExpression, FromExpression)]
+    enum Shape {
+        Circle { center: Point, radius: f64 },
+        Segment(Point, Point),
+        Empty,
+    }
+
+    #[derive(Debug, Clone, PartialEq, IntoExpression, FromExpression)]
+    struct Drawing<T> {
+        name: String,
+        shapes: Vec<Shape>,
+        tag: Option<T>,
+    }
+
+    let drawing = Drawing {
+        name: "sketch".to_string(),
+        shapes: vec![
+            Shape::Circle {
+                center: Point(0, 0),
+                radius: 1.5,
+            },
+            Shape::Segment(Point(1, 2), Point(3, 4)),
+            Shape::Empty,
+        ],
+        tag: Some(7),
+    };
+
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    std.borrow_mut()
+        .set_local("drawing".to_string(), drawing.clone().into_expression());
+
+    let expression = run(&mut std, "drawing").unwrap();
+    let table = expression.as_table().unwrap();
+
+    assert_eq!(
+        table[&Key::from("name")],
+        Expression::String("sketch".into())
+    );
+    assert_eq!(table[&Key::from("tag")], Expression::Integer(7));
+
+    let shapes = table[&Key::from("shapes")].as_list().unwrap();
+    let circle = shapes[0].as_table().unwrap();
+
+    assert_eq!(
+        circle[&Key::from("variant")],
+        Expression::String("Circle".into())
+    );
+    assert_eq!(
+        circle[&Key::from("center")],
+        Expression::List(vec![Expression::Integer(0), Expression::Integer(0)].into())
+    );
+    assert_eq!(
+        shapes[1].as_table().unwrap()[&Key::from("1")],
+        Expression::List(vec![Expression::Integer(3), Expression::Integer(4)].into())
+    );
+
+    assert_eq!(Drawing::from_expression(&expression).unwrap(), drawing);
+
+    let error = Drawing::<i64>::from_expression(&Expression::Table(
+        [(Key::from("name"), Expression::Integer(1))]
+            .into_iter()
+            .collect(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(error.to_string(), "In field `name`");
+
+    let error = Shape::from_expression(&Expression::Table(
+        [(Key::from("variant"), Expression::String("Square".into()))]
+            .into_iter()
+            .collect(),
+    ))
+    .unwrap_err();
+
+    assert_eq!(error.to_string(), "Unknown variant `Square` of Shape");
+}
+
+#[test]
+fn serde_round_trip() {
+    use crate::serialize::{from_expression, to_expression};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Role {
+        Admin,
+        Guest(u32),
+        Member { since: u16 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name: String,
+        age: u8,
+        active: bool,
+        tags: Vec<String>,
+        email: Option<String>,
+        roles: Vec<Role>,
+    }
+
+    let user = User {
+        name: "ada".to_string(),
+        age: 36,
+        active: true,
+        tags: vec!["math".to_string()],
+        email: None,
+        roles: vec![Role::Admin, Role::Guest(3), Role::Member { since: 1843 }],
+    };
+
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    std.borrow_mut()
+        .set_local("user".to_string(), to_expression(&user).unwrap());
+
+    let expression = run(&mut std, "user").unwrap();
+    let table = expression.as_table().unwrap();
+
+    assert_eq!(table[&Key::from("age")], Expression::Integer(36));
+    assert_eq!(table[&Key::from("email")], Expression::Nil);
+    assert_eq!(
+        table[&Key::from("roles")].as_list().unwrap()[0],
+        Expression::String("Admin".into())
+    );
+
+    assert_eq!(from_expression::<User>(&expression).unwrap(), user);
+
+    let expression = run(&mut std, "(list 1 2.5 \"three\" '(4))").unwrap();
+
+    assert_eq!(
+        from_expression::<(i64, f64, String, Vec<u8>)>(&expression).unwrap(),
+        (1, 2.5, "three".to_string(), vec![4])
+    );
+    assert_eq!(
+        from_expression::<Expression>(&expression).unwrap(),
+        expression
+    );
+
+    assert!(from_expression::<User>(&Expression::Integer(1)).is_err());
+    assert!(from_expression::<bool>(&Expression::Integer(1)).is_err());
+    let positions = std::collections::HashMap::from([((1, 2), 3)]);
+
+    assert_eq!(
+        from_expression::<std::collections::HashMap<(i64, i64), i64>>(
+            &to_expression(&positions).unwrap()
+        )
+        .unwrap(),
+        positions
+    );
+    assert!(to_expression(&run(&mut std, "map").unwrap()).is_err());
+}
+
+#[test]
+fn json() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(
+        &mut std,
+        r#"(json/parse "{\"name\": \"ada\", \"tags\": [1, 2.5, null, true, false], \"nested\": {}}")"#,
+    )
+    .unwrap();
+    let table = result.as_table().unwrap();
+
+    assert_eq!(table[&Key::from("name")], Expression::String("ada".into()));
+    assert_eq!(
+        table[&Key::from("tags")],
+        Expression::List(
+            vec![
+                Expression::Integer(1),
+                Expression::Float(2.5),
+                Expression::Nil,
+                Expression::Bool(true),
+                Expression::Bool(false),
+            ]
+            .into()
+        )
+    );
+    assert_eq!(
+        table[&Key::from("nested")],
+        Expression::Table(Default::default())
+    );
+
+    let result = run(&mut std, r#"(json/stringify (list 1 "two" nil t 2.5))"#).unwrap();
+
+    assert_eq!(
+        result,
+        Expression::String(r#"[1,"two",null,true,2.5]"#.into())
+    );
+
+    let result = run(
+        &mut std,
+        r#"(json/stringify (json/parse "{\"a\": [1]}") t)"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        result,
+        Expression::String("{\n  \"a\": [\n    1\n  ]\n}".into())
+    );
+
+    let error = run(&mut std, r#"(json/parse "{\"a\": }")"#).unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Invalid JSON: expected value at line 1 column 7"
+    );
+    assert!(run(&mut std, "(json/stringify map)").is_err());
+}
+
+#[test]
+fn json_keeps_big_integers_exact() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(
+        &mut std,
+        r#"(json/parse "[123456789012345678901234567890, -98765432109876543210, 1e2]")"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        result,
+        run(
+            &mut std,
+            "(list 123456789012345678901234567890 -98765432109876543210 100.0)"
+        )
+        .unwrap()
+    );
+
+    let result = run(
+        &mut std,
+        r#"(json/stringify (json/parse "123456789012345678901234567890"))"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        result,
+        Expression::String("123456789012345678901234567890".into())
+    );
+}
+
+#[test]
+fn json_refuses_non_finite_numbers() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let error = run(&mut std, "(json/stringify (list 1 nan))").unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Can't encode JSON: nan is not a finite number"
+    );
+
+    let error = run(&mut std, r#"(json/stringify { "a" -inf })"#).unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Can't encode JSON: -inf is not a finite number"
+    );
+}
+
+#[test]
+fn table_get() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(&mut std, r#"(define 'person { name "ada" age 36 })"#).unwrap();
+
+    assert_eq!(
+        run(&mut std, "(get person 'name)").unwrap(),
+        Expression::String("ada".into())
+    );
+    assert_eq!(
+        run(&mut std, "(get person 'missing)").unwrap(),
+        Expression::Nil
+    );
+    assert_eq!(
+        run(&mut std, "(get person 'missing 0)").unwrap(),
+        Expression::Integer(0)
+    );
+}
+
+#[test]
+fn table_keys_of_different_types_are_different() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(&mut std, r#"(define 'person { name "ada" age 36 })"#).unwrap();
+
+    let result = run(&mut std, r#"(get person "age")"#).unwrap();
+
+    assert_eq!(result, Expression::Nil);
+}
+
+#[test]
+fn table_get_in() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(
+        &mut std,
+        r#"(define 'person { name "ada" address { city "london" } })"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        run(&mut std, "(get-in person '(address city))").unwrap(),
+        Expression::String("london".into())
+    );
+    assert_eq!(
+        run(&mut std, r#"(get-in person '(address zip) "none")"#).unwrap(),
+        Expression::String("none".into())
+    );
+}
+
+#[test]
+fn table_has_key() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(&mut std, r#"(define 'person { name "ada" age 36 })"#).unwrap();
+
+    assert_eq!(
+        run(&mut std, "(has-key person 'age)").unwrap(),
+        Expression::Bool(true)
+    );
+    assert_eq!(
+        run(&mut std, "(has-key (dissoc person 'age) 'age)").unwrap(),
+        Expression::Bool(false)
+    );
+}
+
+#[test]
+fn table_assoc_update_and_merge() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(&mut std, r#"(define 'person { name "ada" age 36 })"#).unwrap();
+
+    assert_eq!(
+        run(&mut std, r#"(get (assoc person 'age 37 'job "math") 'job)"#).unwrap(),
+        Expression::String("math".into())
+    );
+    assert_eq!(
+        run(&mut std, "(get (update person 'age (+ 1 _)) 'age)").unwrap(),
+        Expression::Integer(37)
+    );
+    assert_eq!(
+        run(&mut std, "(get (merge person { age 40 }) 'age)").unwrap(),
+        Expression::Integer(40)
+    );
+}
+
+#[test]
+fn table_changes_leave_the_original_alone() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(&mut std, r#"(define 'person { name "ada" age 36 })"#).unwrap();
+    run(&mut std, "(assoc person 'age 37)").unwrap();
+    run(&mut std, "(update person 'age (+ 1 _))").unwrap();
+    run(&mut std, "(dissoc person 'age)").unwrap();
+
+    let result = run(&mut std, "(get person 'age)").unwrap();
+
+    assert_eq!(result, Expression::Integer(36));
+}
+
+#[test]
+fn table_keys_values_and_entries() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(&mut std, r#"(define 'person { name "ada" age 36 })"#).unwrap();
+
+    assert_eq!(
+        run(&mut std, "(keys person)").unwrap().to_string(),
+        "(name age)"
+    );
+    assert_eq!(
+        run(&mut std, "(values person)").unwrap().to_string(),
+        "(\"ada\" 36)"
+    );
+    assert_eq!(
+        run(&mut std, "(entries { a 1 })").unwrap(),
+        Expression::List(
+            vec![Expression::List(
+                vec![Expression::Symbol("a".into()), Expression::Integer(1)].into(),
+            )]
+            .into(),
+        )
+    );
+}
+
+#[test]
+fn tables_are_equal_by_contents() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(= {} (table))").unwrap(),
+        Expression::Bool(true)
+    );
+    assert_eq!(
+        run(&mut std, "(= { a 1 b 2 } { b 2 a 1 })").unwrap(),
+        Expression::Bool(true)
+    );
+}
+
+#[test]
+fn table_literals_read_back_as_they_print() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let literal = run(&mut std, r#"{ "a" 1 :b 2 c (1 "x" d) e { f #\a } }"#).unwrap();
+
+    assert_eq!(
+        run(&mut std, r#"'{ "a" 1 :b 2 c (1 "x" d) e { f #\a } }"#).unwrap(),
+        literal
+    );
+    assert_eq!(run(&mut std, &literal.to_string()).unwrap(), literal);
+}
+
+#[test]
+fn table_literals_are_not_evaluated() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(get { sum (+ 1 2) } 'sum)").unwrap(),
+        run(&mut std, "'(+ 1 2)").unwrap()
+    );
+    assert_eq!(
+        run(&mut std, "(get (table 'sum (+ 1 2)) 'sum)").unwrap(),
+        Expression::Integer(3)
+    );
+}
+
+#[test]
+fn malformed_tables_are_errors() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert!(run(&mut std, "{ a }").is_err());
+    assert!(run(&mut std, "{ 1.5 2 }").is_err());
+    assert!(run(&mut std, "(get '(1 2) 'a)").is_err());
+}
+
+#[test]
+fn table_keys() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(
+        &mut std,
+        r#"(define 'keyed { 1 "int" "a" "string" a "symbol" :a "keyword" (1 (a)) "list" })"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        run(&mut std, "(get keyed 1)").unwrap(),
+        Expression::String("int".into())
+    );
+    assert_eq!(
+        run(&mut std, r#"(get keyed "a")"#).unwrap(),
+        Expression::String("string".into())
+    );
+    assert_eq!(
+        run(&mut std, "(get keyed 'a)").unwrap(),
+        Expression::String("symbol".into())
+    );
+    assert_eq!(
+        run(&mut std, "(get keyed :a)").unwrap(),
+        Expression::String("keyword".into())
+    );
+    assert_eq!(run(&mut std, "(get keyed 2)").unwrap(), Expression::Nil);
+}
+
+#[test]
+fn lists_are_table_keys_by_value() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(&mut std, r#"(define 'keyed { (1 (a)) "list" })"#).unwrap();
+
+    assert_eq!(
+        run(&mut std, "(get keyed '(1 (a)))").unwrap(),
+        Expression::String("list".into())
+    );
+    assert_eq!(
+        run(&mut std, "(get keyed (list 1 (list 'a)))").unwrap(),
+        Expression::String("list".into())
+    );
+    assert!(run(&mut std, "(get keyed '(1 2.5))").is_err());
+}
+
+#[test]
+fn keywords() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, ":a").unwrap(),
+        Expression::Keyword("a".into())
+    );
+    assert_eq!(
+        run(&mut std, "(type :a)").unwrap(),
+        Expression::String("keyword".into())
+    );
+}
+
+#[test]
+fn tables_keep_insertion_order() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, "{ z 1 a 2 m 3 }").unwrap();
+
+    assert_eq!(result.to_string(), "{ z 1 a 2 m 3 }");
+}
+
+#[test]
+fn table_changes_keep_insertion_order() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(keys (assoc { z 1 a 2 } 'b 3 'z 4))")
+            .unwrap()
+            .to_string(),
+        "(z a b)"
+    );
+    assert_eq!(
+        run(&mut std, "(values (dissoc { z 1 a 2 m 3 } 'a))")
+            .unwrap()
+            .to_string(),
+        "(1 3)"
+    );
+    assert_eq!(
+        run(&mut std, "(keys (merge { b 1 } { a 2 b 3 }))")
+            .unwrap()
+            .to_string(),
+        "(b a)"
+    );
+}
+
+#[test]
+fn json_keeps_insertion_order() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(
+            &mut std,
+            r#"(json/stringify { "z" 1 "a" { "y" 2 "b" 3 } })"#
+        )
+        .unwrap(),
+        Expression::String(r#"{"z":1,"a":{"y":2,"b":3}}"#.into())
+    );
+    assert_eq!(
+        run(
+            &mut std,
+            r#"(json/stringify (json/parse "{\"z\": 1, \"a\": 2}"))"#
+        )
+        .unwrap(),
+        Expression::String(r#"{"z":1,"a":2}"#.into())
+    );
+}
+
+#[test]
+fn env_lists_definitions_in_order() {
+    let std = Rc::new(RefCell::new(std_lib()));
+    let mut scope = Rc::new(RefCell::new(crate::env::Env::new(Some(std))));
+
+    run(&mut scope, "(define-local 'b 2)").unwrap();
+    run(&mut scope, "(define-local 'a 1)").unwrap();
+    run(&mut scope, "(define-local 'c 3)").unwrap();
+
+    assert_eq!(
+        run(&mut scope, "(env)").unwrap().to_string(),
+        "({} {} 1 2 3)"
+    );
+}
+
+#[test]
+fn boolean_literals() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(run(&mut std, "true").unwrap(), Expression::Bool(true));
+    assert_eq!(run(&mut std, "#f").unwrap(), Expression::Bool(false));
+    assert_eq!(run(&mut std, "t").unwrap(), Expression::Bool(true));
+    assert_eq!(
+        run(&mut std, "(type false)").unwrap(),
+        Expression::String("boolean".into())
+    );
+    assert_eq!(
+        run(&mut std, "(list true false)").unwrap().to_string(),
+        "(true false)"
+    );
+}
+
+#[test]
+fn comparisons_return_booleans() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(run(&mut std, "(< 1 2)").unwrap(), Expression::Bool(true));
+    assert_eq!(run(&mut std, "(= 1 2)").unwrap(), Expression::Bool(false));
+    assert_eq!(
+        run(&mut std, "(= false nil)").unwrap(),
+        Expression::Bool(false)
+    );
+}
+
+#[test]
+fn only_false_and_nil_are_falsy() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(if false 1 2)").unwrap(),
+        Expression::Integer(2)
+    );
+    assert_eq!(
+        run(&mut std, "(if nil 1 2)").unwrap(),
+        Expression::Integer(2)
+    );
+    assert_eq!(run(&mut std, "(if 0 1 2)").unwrap(), Expression::Integer(1));
+    assert_eq!(
+        run(&mut std, r#"(if "" 1 2)"#).unwrap(),
+        Expression::Integer(1)
+    );
+    assert_eq!(
+        run(&mut std, "(if '() 1 2)").unwrap(),
+        Expression::Integer(1)
+    );
+}
+
+#[test]
+fn and_or_return_booleans() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(and true 1)").unwrap(),
+        Expression::Bool(true)
+    );
+    assert_eq!(
+        run(&mut std, "(or false nil)").unwrap(),
+        Expression::Bool(false)
+    );
+}
+
+#[test]
+fn booleans_are_table_keys() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, "(get { true yes } (> 2 1))").unwrap();
+
+    assert_eq!(result, Expression::Symbol("yes".into()));
+}
+
+#[test]
+fn symbols_starting_with_true_are_symbols() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, "(define 'true-count 3) true-count").unwrap();
+
+    assert_eq!(result, Expression::Integer(3));
+}
+
+#[test]
+fn integers_overflow_into_bignums() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(+ 9223372036854775807 1)")
+            .unwrap()
+            .to_string(),
+        "9223372036854775808"
+    );
+    assert_eq!(
+        run(&mut std, "(- -9223372036854775808 1)")
+            .unwrap()
+            .to_string(),
+        "-9223372036854775809"
+    );
+    assert_eq!(
+        run(&mut std, "(/ -9223372036854775808 -1)")
+            .unwrap()
+            .to_string(),
+        "9223372036854775808"
+    );
+    assert_eq!(
+        run(&mut std, "(% -9223372036854775808 -1)").unwrap(),
+        Expression::Integer(0)
+    );
+}
+
+#[test]
+fn factorials_grow_into_bignums() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    run(
+        &mut std,
+        "(define 'factorial (function '(n) '(if (<= n 1) 1 (* n (factorial (- n 1))))))",
+    )
+    .unwrap();
+
+    assert_eq!(
+        run(&mut std, "(factorial 20)").unwrap(),
+        Expression::Integer(2432902008176640000)
+    );
+    assert_eq!(
+        run(&mut std, "(factorial 25)").unwrap().to_string(),
+        "15511210043330985984000000"
+    );
+    assert_eq!(
+        run(&mut std, "(/ (factorial 25) (factorial 23))").unwrap(),
+        Expression::Integer(600)
+    );
+    assert_eq!(
+        run(&mut std, "(% (factorial 25) 7)").unwrap(),
+        Expression::Integer(0)
+    );
+}
+
+#[test]
+fn bignum_literals() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "123456789012345678901234567890")
+            .unwrap()
+            .to_string(),
+        "123456789012345678901234567890"
+    );
+    assert_eq!(
+        run(&mut std, "-123456789012345678901234567890")
+            .unwrap()
+            .to_string(),
+        "-123456789012345678901234567890"
+    );
+    assert_eq!(
+        run(&mut std, "(type 99999999999999999999)").unwrap(),
+        Expression::String("integer".into())
+    );
+}
+
+#[test]
+fn bignums_shrink_back_to_integers() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(
+            &mut std,
+            "(- 123456789012345678901234567890 123456789012345678901234567889)"
+        )
+        .unwrap(),
+        Expression::Integer(1)
+    );
+    assert_eq!(
+        run(&mut std, "(- 9223372036854775808 1)").unwrap(),
+        Expression::Integer(i64::MAX)
+    );
+}
+
+#[test]
+fn bignums_mix_with_floats() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(* 10000000000000000000 0.5)").unwrap(),
+        Expression::Float(5e18)
+    );
+    assert_eq!(
+        run(&mut std, "(< 9223372036854775807 9223372036854775808)").unwrap(),
+        Expression::Bool(true)
+    );
+}
+
+#[test]
+fn bignum_indices_are_errors() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert!(run(&mut std, "(nth 99999999999999999999 '(1))").is_err());
+}
+
+#[test]
+fn bignums_convert_to_and_from_rust() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let big = num_bigint::BigInt::from(u64::MAX) * 4u8;
+
+    assert_eq!(
+        num_bigint::BigInt::from_expression(&big.clone().into_expression()).unwrap(),
+        big
+    );
+    assert_eq!(
+        crate::serialize::from_expression::<u64>(&run(&mut std, "18446744073709551615").unwrap())
+            .unwrap(),
+        u64::MAX
+    );
+
+    let big = run(&mut std, "(* 18446744073709551615 4)").unwrap();
+
+    assert_eq!(crate::serialize::to_expression(&big).unwrap(), big);
+    assert_eq!(
+        crate::serialize::to_expression(&u64::MAX).unwrap(),
+        run(&mut std, "18446744073709551615").unwrap()
+    );
+    assert_eq!(
+        crate::serialize::to_expression(&i128::MIN).unwrap(),
+        run(&mut std, "-170141183460469231731687303715884105728").unwrap()
+    );
+}
+
+#[test]
+fn integer_division_is_exact() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(run(&mut std, "(/ 1 3)").unwrap().to_string(), "1/3");
+    assert_eq!(run(&mut std, "(/ 6 3)").unwrap(), Expression::Integer(2));
+    assert_eq!(run(&mut std, "(/ 6 4)").unwrap().to_string(), "3/2");
+    assert_eq!(run(&mut std, "(/ -6 4)").unwrap().to_string(), "-3/2");
+    assert_eq!(
+        run(&mut std, "(/ (* 99999999999999999999 2) 4)")
+            .unwrap()
+            .to_string(),
+        "99999999999999999999/2"
+    );
+}
+
+#[test]
+fn rational_arithmetic() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(+ 1/3 2/3)").unwrap(),
+        Expression::Integer(1)
+    );
+    assert_eq!(run(&mut std, "(* 2/3 3/4)").unwrap().to_string(), "1/2");
+    assert_eq!(run(&mut std, "(- 1/2 1)").unwrap().to_string(), "-1/2");
+    assert_eq!(
+        run(&mut std, "(/ 1/2 1/4)").unwrap(),
+        Expression::Integer(2)
+    );
+    assert_eq!(run(&mut std, "(% 7/2 1)").unwrap().to_string(), "1/2");
+}
+
+#[test]
+fn rationals_become_floats_with_floats() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(+ 1/2 0.25)").unwrap(),
+        Expression::Float(0.75)
+    );
+    assert_eq!(run(&mut std, "(- 1.5 1)").unwrap(), Expression::Float(0.5));
+    assert_eq!(
+        run(&mut std, "(exact->inexact 1/4)").unwrap(),
+        Expression::Float(0.25)
+    );
+    assert_eq!(
+        run(&mut std, "(exact->inexact 3)").unwrap(),
+        Expression::Float(3.0)
+    );
+}
+
+#[test]
+fn numerator_and_denominator() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(numerator 6/4)").unwrap(),
+        Expression::Integer(3)
+    );
+    assert_eq!(
+        run(&mut std, "(denominator 6/4)").unwrap(),
+        Expression::Integer(2)
+    );
+    assert_eq!(
+        run(&mut std, "(denominator 5)").unwrap(),
+        Expression::Integer(1)
+    );
+    assert!(run(&mut std, "(numerator 0.5)").is_err());
+}
+
+#[test]
+fn rational_type() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, "(type 1/2)").unwrap();
+
+    assert_eq!(result, Expression::String("rational".into()));
+}
+
+#[test]
+fn numbers_of_different_types_compare_exactly() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(run(&mut std, "(< 1 1.5)").unwrap(), Expression::Bool(true));
+    assert_eq!(run(&mut std, "(> 2 1.5)").unwrap(), Expression::Bool(true));
+    assert_eq!(
+        run(&mut std, "(< 1/3 0.3333)").unwrap(),
+        Expression::Bool(false)
+    );
+    assert_eq!(
+        run(&mut std, "(< 9007199254740993 9007199254740992.0)").unwrap(),
+        Expression::Bool(false)
+    );
+    assert_eq!(
+        run(&mut std, "(> 9007199254740993 9007199254740992.0)").unwrap(),
+        Expression::Bool(true)
+    );
+    assert_eq!(
+        run(&mut std, "(< 1 (/ 1.0 0.0001))").unwrap(),
+        Expression::Bool(true)
+    );
+}
+
+#[test]
+fn numbers_of_different_types_are_equal_by_value() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(run(&mut std, "(= 1 1.0)").unwrap(), Expression::Bool(true));
+    assert_eq!(
+        run(&mut std, "(= 1/2 0.5)").unwrap(),
+        Expression::Bool(true)
+    );
+    assert_eq!(run(&mut std, "(= 2/2 1)").unwrap(), Expression::Bool(true));
+    assert_eq!(
+        run(&mut std, "(= 1/3 0.3333333333333333)").unwrap(),
+        Expression::Bool(false)
+    );
+    assert_eq!(
+        run(&mut std, "(= '(1 2) '(1.0 2))").unwrap(),
+        Expression::Bool(true)
+    );
+    assert_eq!(
+        run(&mut std, "(= 1 \"1\")").unwrap(),
+        Expression::Bool(false)
+    );
+}
+
+#[test]
+fn dividing_by_zero_is_an_error() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert!(run(&mut std, "(/ 1 0)").is_err());
+    assert!(run(&mut std, "1/0").is_err());
+}
+
+#[test]
+fn float_literals() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(run(&mut std, "1e10").unwrap(), Expression::Float(1e10));
+    assert_eq!(run(&mut std, "2.5E-3").unwrap(), Expression::Float(2.5e-3));
+    assert_eq!(run(&mut std, "-1.5e+2").unwrap(), Expression::Float(-150.0));
+    assert_eq!(run(&mut std, ".5").unwrap(), Expression::Float(0.5));
+    assert_eq!(run(&mut std, "-.5").unwrap(), Expression::Float(-0.5));
+    assert_eq!(run(&mut std, "1.").unwrap(), Expression::Float(1.0));
+}
+
+#[test]
+fn radix_literals() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(run(&mut std, "0xFF").unwrap(), Expression::Integer(255));
+    assert_eq!(run(&mut std, "-0x10").unwrap(), Expression::Integer(-16));
+    assert_eq!(run(&mut std, "0o17").unwrap(), Expression::Integer(15));
+    assert_eq!(run(&mut std, "0b1010").unwrap(), Expression::Integer(10));
+}
+
+#[test]
+fn digit_separators() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "1_000_000").unwrap(),
+        Expression::Integer(1_000_000)
+    );
+    assert_eq!(
+        run(&mut std, "0xffff_ffff").unwrap(),
+        Expression::Integer(0xffff_ffff)
+    );
+    assert_eq!(
+        run(&mut std, "1_000.000_1").unwrap(),
+        Expression::Float(1000.0001)
+    );
+}
+
+#[test]
+fn signed_literals() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(run(&mut std, "+5").unwrap(), Expression::Integer(5));
+    assert_eq!(
+        run(&mut std, "+1/2").unwrap(),
+        run(&mut std, "(/ 1 2)").unwrap()
+    );
+    assert_eq!(run(&mut std, "(+ 1 +2)").unwrap(), Expression::Integer(3));
+    assert_eq!(run(&mut std, "(- 5 -2)").unwrap(), Expression::Integer(7));
+}
+
+#[test]
+fn infinity_and_nan() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "inf").unwrap(),
+        Expression::Float(f64::INFINITY)
+    );
+    assert_eq!(
+        run(&mut std, "-inf").unwrap(),
+        Expression::Float(f64::NEG_INFINITY)
+    );
+    assert!(matches!(run(&mut std, "nan").unwrap(), Expression::Float(f) if f.is_nan()));
+}
+
+#[test]
+fn symbols_may_start_like_numbers() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(define 'info 4) info").unwrap(),
+        Expression::Integer(4)
+    );
+    assert_eq!(
+        run(&mut std, "(define '-x 4) (- 0 -x)").unwrap(),
+        Expression::Integer(-4)
+    );
+}
+
+#[test]
+fn floats_print_as_they_read() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(run(&mut std, "1e300").unwrap().to_string(), "1e300");
+    assert_eq!(run(&mut std, "inf").unwrap().to_string(), "inf");
+    assert_eq!(run(&mut std, "-inf").unwrap().to_string(), "-inf");
+    assert_eq!(run(&mut std, "nan").unwrap().to_string(), "nan");
+    assert_eq!(run(&mut std, "0.5").unwrap().to_string(), "0.5");
+}
+
+#[test]
+fn malformed_number_literals_are_errors() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert!(run(&mut std, "1abc").is_err());
+    assert!(run(&mut std, "0xZZ").is_err());
+    assert!(run(&mut std, "1__000").is_err());
+    assert!(run(&mut std, "1_").is_err());
+    assert!(run(&mut std, "1e").is_err());
+}
+
+#[test]
+fn to_string_with_a_radix() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(to-string 255 16)").unwrap(),
+        Expression::String("ff".into())
+    );
+    assert_eq!(
+        run(&mut std, "(to-string -10 2)").unwrap(),
+        Expression::String("-1010".into())
+    );
+    assert_eq!(
+        run(&mut std, "(to-string 0x1_0000_0000_0000_0000 16)").unwrap(),
+        Expression::String("10000000000000000".into())
+    );
+    assert!(run(&mut std, "(to-string 1.5 2)").is_err());
+    assert!(run(&mut std, "(to-string 1 1)").is_err());
+}
+
+#[test]
+fn char_literals() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(run(&mut std, "#\\a").unwrap(), Expression::Char('a'));
+    assert_eq!(run(&mut std, "#\\λ").unwrap(), Expression::Char('λ'));
+    assert_eq!(run(&mut std, "#\\space").unwrap(), Expression::Char(' '));
+    assert_eq!(run(&mut std, "#\\newline").unwrap(), Expression::Char('\n'));
+    assert_eq!(run(&mut std, "#\\u{3bb}").unwrap(), Expression::Char('λ'));
+    assert_eq!(run(&mut std, "#\\(").unwrap(), Expression::Char('('));
+    assert_eq!(
+        run(&mut std, "(type #\\a)").unwrap(),
+        Expression::String("char".into())
+    );
+}
+
+#[test]
+fn chars_print_as_they_read() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(run(&mut std, "#\\a").unwrap().to_string(), "#\\a");
+    assert_eq!(run(&mut std, "#\\space").unwrap().to_string(), "#\\space");
+    assert_eq!(
+        run(&mut std, "#\\newline").unwrap().to_string(),
+        "#\\newline"
+    );
+    assert_eq!(run(&mut std, "#\\u{7}").unwrap().to_string(), "#\\u{7}");
+    assert_eq!(run(&mut std, "#\\λ").unwrap().to_string(), "#\\λ");
+}
+
+#[test]
+fn malformed_chars_are_errors() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert!(run(&mut std, "#\\ab").is_err());
+    assert!(run(&mut std, "#\\spaces").is_err());
+}
+
+#[test]
+fn chars_convert_to_and_from_integers() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(char->integer #\\a)").unwrap(),
+        Expression::Integer(97)
+    );
+    assert_eq!(
+        run(&mut std, "(integer->char 955)").unwrap(),
+        Expression::Char('λ')
+    );
+    assert!(run(&mut std, "(integer->char -1)").is_err());
+    assert!(run(&mut std, "(integer->char 55296)").is_err());
+}
+
+#[test]
+fn chars_work_with_strings_and_tables() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(concat \"ab\" #\\c)").unwrap(),
+        Expression::String("abc".into())
+    );
+    assert_eq!(
+        run(&mut std, "(= #\\a #\\a)").unwrap(),
+        Expression::Bool(true)
+    );
+    assert_eq!(
+        run(&mut std, "(get { #\\a 1 } #\\a)").unwrap(),
+        Expression::Integer(1)
+    );
+}
+
+#[test]
+fn strings_are_indexed_by_character() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(length \"héllo\")").unwrap(),
+        Expression::Integer(5)
+    );
+    assert_eq!(
+        run(&mut std, "(nth 1 \"héllo\")").unwrap(),
+        Expression::Char('é')
+    );
+    assert_eq!(
+        run(&mut std, "(slice 1 3 \"héllo\")").unwrap(),
+        Expression::String("él".into())
+    );
+    assert_eq!(
+        run(&mut std, "(reverse \"héllo\")").unwrap(),
+        Expression::String("olléh".into())
+    );
+    assert_eq!(
+        run(&mut std, "(length \"e\\u{301}\")").unwrap(),
+        Expression::Integer(2)
+    );
+}
+
+#[test]
+fn out_of_range_string_indices_are_errors() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert!(run(&mut std, "(nth 5 \"héllo\")").is_err());
+    assert!(run(&mut std, "(slice 2 1 \"héllo\")").is_err());
+    assert!(run(&mut std, "(slice 1 9223372036854775807 \"a\")").is_err());
+    assert!(run(&mut std, "(slice 1 9223372036854775807 '(1))").is_err());
+}
+
+#[test]
+fn split_strings() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(split \"\" \"hé\")").unwrap().to_string(),
+        "(\"h\" \"é\")"
+    );
+    assert_eq!(
+        run(&mut std, "(split #\\, \"a,é\")").unwrap().to_string(),
+        "(\"a\" \"é\")"
+    );
+    assert_eq!(
+        run(&mut std, "(split \", \" \"a, b\")")
+            .unwrap()
+            .to_string(),
+        "(\"a\" \"b\")"
+    );
+}
+
+#[test]
+fn graphemes() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(grapheme/split \"e\\u{301}x\")").unwrap(),
+        Expression::List(
+            vec![
+                Expression::String("e\u{301}".into()),
+                Expression::String("x".into())
+            ]
+            .into()
+        )
+    );
+    assert_eq!(
+        run(&mut std, "(grapheme/length \"e\\u{301}x\")").unwrap(),
+        Expression::Integer(2)
+    );
+    assert_eq!(
+        run(&mut std, "(grapheme/nth 0 \"e\\u{301}x\")").unwrap(),
+        Expression::String("e\u{301}".into())
+    );
+    assert_eq!(
+        run(&mut std, "(grapheme/slice 1 2 \"e\\u{301}xy\")").unwrap(),
+        Expression::String("x".into())
+    );
+    assert_eq!(
+        run(&mut std, "(grapheme/reverse \"e\\u{301}x\")").unwrap(),
+        Expression::String("xe\u{301}".into())
+    );
+}
+
+#[test]
+fn out_of_range_grapheme_indices_are_errors() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert!(run(&mut std, "(grapheme/nth 2 \"e\\u{301}x\")").is_err());
+    assert!(run(&mut std, "(grapheme/slice 1 9223372036854775807 \"a\")").is_err());
+}
+
+#[test]
+fn string_trim_and_case() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(string/trim \"  hi \\n\")").unwrap(),
+        Expression::String("hi".into())
+    );
+    assert_eq!(
+        run(&mut std, "(string/upper \"straße\")").unwrap(),
+        Expression::String("STRASSE".into())
+    );
+    assert_eq!(
+        run(&mut std, "(string/lower \"ÉCOLE\")").unwrap(),
+        Expression::String("école".into())
+    );
+    assert!(run(&mut std, "(string/upper 1)").is_err());
+}
+
+#[test]
+fn string_search() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(string/starts-with \"ab\" \"abc\")").unwrap(),
+        Expression::Bool(true)
+    );
+    assert_eq!(
+        run(&mut std, "(string/ends-with \"ab\" \"abc\")").unwrap(),
+        Expression::Bool(false)
+    );
+    assert_eq!(
+        run(&mut std, "(string/contains \"é\" \"café\")").unwrap(),
+        Expression::Bool(true)
+    );
+    assert_eq!(
+        run(&mut std, "(string/index-of \"l\" \"héllo\")").unwrap(),
+        Expression::Integer(2)
+    );
+    assert_eq!(
+        run(&mut std, "(string/index-of \"z\" \"héllo\")").unwrap(),
+        Expression::Nil
+    );
+}
+
+#[test]
+fn string_replace() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, "(string/replace \"o\" \"0\" \"foo\")").unwrap();
+
+    assert_eq!(result, Expression::String("f00".into()));
+}
+
+#[test]
+fn string_padding() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(string/pad-left 5 \"42\")").unwrap(),
+        Expression::String("   42".into())
+    );
+    assert_eq!(
+        run(&mut std, "(string/pad-left 5 #\\0 \"42\")").unwrap(),
+        Expression::String("00042".into())
+    );
+    assert_eq!(
+        run(&mut std, "(string/pad-right 4 #\\. \"é\")").unwrap(),
+        Expression::String("é...".into())
+    );
+    assert_eq!(
+        run(&mut std, "(string/pad-left 1 \"long\")").unwrap(),
+        Expression::String("long".into())
+    );
+}
+
+#[test]
+fn string_repeat() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(string/repeat 3 \"ab\")").unwrap(),
+        Expression::String("ababab".into())
+    );
+    assert!(run(&mut std, "(string/repeat -1 \"a\")").is_err());
+}
+
+#[test]
+fn oversized_strings_are_errors() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert!(run(&mut std, "(string/repeat 9223372036854775807 \"ab\")").is_err());
+    assert!(run(&mut std, "(string/repeat 2000000000 \"a\")").is_err());
+    assert!(run(&mut std, "(string/pad-left 9223372036854775807 \"a\")").is_err());
+    assert!(run(
+        &mut std,
+        "(string/pad-right 9223372036854775807 #\\é \"a\")"
+    )
+    .is_err());
+}
+
+#[test]
+fn string_join() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, "(string/join \", \" '(\"a\" #\\b 3))").unwrap();
+
+    assert_eq!(result, Expression::String("a, b, 3".into()));
+}
+
+#[test]
+fn string_lines_and_chars() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(string/lines \"a\\nb\\r\\nc\")")
+            .unwrap()
+            .to_string(),
+        "(\"a\" \"b\" \"c\")"
+    );
+    assert_eq!(
+        run(&mut std, "(string/chars \"hé\")").unwrap(),
+        Expression::List(vec![Expression::Char('h'), Expression::Char('é')].into())
+    );
+}
+
+#[test]
+fn string_parse_int() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(string/parse-int \" -42 \")").unwrap(),
+        Expression::Integer(-42)
+    );
+    assert_eq!(
+        run(&mut std, "(string/parse-int 16 \"ff\")").unwrap(),
+        Expression::Integer(255)
+    );
+    assert_eq!(
+        run(&mut std, "(string/parse-int \"100000000000000000000\")").unwrap(),
+        run(&mut std, "100000000000000000000").unwrap()
+    );
+    assert!(run(&mut std, "(string/parse-int \"12a\")").is_err());
+    assert!(run(&mut std, "(string/parse-int 37 \"1\")").is_err());
+}
+
+#[test]
+fn string_parse_float() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(string/parse-float \"2.5e1\")").unwrap(),
+        Expression::Float(25.0)
+    );
+    assert!(run(&mut std, "(string/parse-float \"abc\")").is_err());
+}
+
+#[test]
+fn string_format() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(string/format \"{} + {} = {}\" 1 \"two\" '(3))").unwrap(),
+        Expression::String("1 + two = (3)".into())
+    );
+    assert_eq!(
+        run(&mut std, "(string/format \"{{}} {}\" #\\x)").unwrap(),
+        Expression::String("{} x".into())
+    );
+}
+
+#[test]
+fn string_format_needs_a_value_per_placeholder() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert!(run(&mut std, "(string/format \"{} {}\" 1)").is_err());
+    assert!(run(&mut std, "(string/format \"{}\" 1 2)").is_err());
+}
+
+#[test]
+fn string_builtins_take_the_string_last() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, "(map (string/pad-left 3) '(\"1\" \"22\"))").unwrap();
+
+    assert_eq!(result, run(&mut std, "'(\"  1\" \" 22\")").unwrap());
+
+    let result = run(
+        &mut std,
+        "(map (string/pad-right 3 #\\. _) '(\"1\" \"22\"))",
+    )
+    .unwrap();
+
+    assert_eq!(result, run(&mut std, "'(\"1..\" \"22.\")").unwrap());
+
+    let result = run(&mut std, "(map (string/parse-int 2 _) '(\"10\" \"11\"))").unwrap();
+
+    assert_eq!(result, run(&mut std, "'(2 3)").unwrap());
+}
+
+#[test]
+fn regex_match() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(&mut std, "(regex/match \"[0-9]+\" \"id 42, 7\")").unwrap(),
+        Expression::String("42".into())
+    );
+    assert_eq!(
+        run(&mut std, "(regex/match \"[0-9]+\" \"none\")").unwrap(),
+        Expression::Nil
+    );
+    assert_eq!(
+        run(&mut std, "(map (regex/match \"^\\\\w\") '(\"ab\" \"cd\"))")
+            .unwrap()
+            .to_string(),
+        "(\"a\" \"c\")"
+    );
+}
+
+#[test]
+fn regex_find_all() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, "(regex/find-all \"[0-9]+\" \"id 42, 7\")").unwrap();
+
+    assert_eq!(result.to_string(), "(\"42\" \"7\")");
+}
+
+#[test]
+fn regex_captures() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(
+            &mut std,
+            "(regex/captures \"(?P<key>\\\\w+)=(?P<value>\\\\w*)(?P<rest>;)?\" \"a=1\")"
+        )
+        .unwrap(),
+        run(&mut std, "{ \"key\" \"a\" \"value\" \"1\" \"rest\" nil }").unwrap()
+    );
+    assert_eq!(
+        run(&mut std, "(regex/captures \"(?P<x>a)\" \"b\")").unwrap(),
+        Expression::Nil
+    );
+}
+
+#[test]
+fn regex_replace() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert_eq!(
+        run(
+            &mut std,
+            "(regex/replace \"(\\\\w+)@\" \"$1 at \" \"me@host\")"
+        )
+        .unwrap(),
+        Expression::String("me at host".into())
+    );
+    assert_eq!(
+        run(
+            &mut std,
+            "(regex/replace \"[0-9]+\" (function '(n) '(to-string (* 2 (string/parse-int n)))) \"1 and 21\")"
+        )
+        .unwrap(),
+        Expression::String("2 and 42".into())
+    );
+    assert!(run(&mut std, "(regex/replace \"a\" (function '(m) 1) \"a\")").is_err());
+}
+
+#[test]
+fn regex_split() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    let result = run(&mut std, "(regex/split \"\\\\s*,\\\\s*\" \"a , b,c\")").unwrap();
+
+    assert_eq!(result.to_string(), "(\"a\" \"b\" \"c\")");
+}
+
+#[test]
+fn invalid_regexes_are_errors() {
+    let mut std = Rc::new(RefCell::new(std_lib()));
+
+    assert!(run(&mut std, "(regex/match \"(\" \"a\")").is_err());
 }